@@ -0,0 +1,78 @@
+use criterion::measurement::Measurement;
+use criterion::*;
+use lazy_static::lazy_static;
+use support::Bench;
+use wasm_runtime_benchmark::dispatch::{self, Once};
+use wasm_runtime_benchmark::harness::{self, Budget};
+use wasm_runtime_benchmark::matrix::{Case, Phase, Runtime, Selection};
+use wasm_runtime_benchmark::synth::Dimension;
+
+#[macro_use]
+mod support;
+
+lazy_static! {
+    static ref BUDGET: Budget = Budget::from_env();
+    static ref SELECTION: Selection = Selection::from_env().expect("invalid selection");
+}
+
+// One group per dimension of the generated modules, with the module size in
+// bytes as parameter so the summary plots compile time against it. Ids for the
+// budget are `compile_scaling/dimension/runtime/bytes`.
+fn bench_dimension<M: Measurement>(c: &mut Criterion<M>, dimension: Dimension) {
+    let runtimes: Vec<Runtime> = SELECTION
+        .runtimes()
+        .into_iter()
+        .filter(|r| Phase::Compile.supports(*r) && *r != Runtime::Native)
+        .collect();
+
+    let mut group = support::group(c, format!("compile_scaling_{}", dimension));
+    group.sample_size(10);
+
+    for &step in dimension.steps() {
+        let wasm = dimension.shape(step).wasm();
+        group.throughput(Throughput::Bytes(wasm.len() as u64));
+
+        for &runtime in &runtimes {
+            let case = Case {
+                phase: Phase::Compile,
+                sample: format!("synth-{}-{}", dimension, step),
+                runtime,
+                export: "run".to_owned(),
+                args: vec![10],
+                native: None,
+            };
+            let id = format!("compile_scaling/{}/{}/{}", dimension, runtime, wasm.len());
+            let probe = {
+                let case = case.clone();
+                let wasm = wasm.clone();
+                move || {
+                    let mut timer = Once::default();
+                    dispatch::run(&case, &wasm, &mut timer);
+                    timer.0
+                }
+            };
+            if !BUDGET.admit(&id, probe) {
+                continue;
+            }
+
+            if let Some(size) = dispatch::artifact_size(runtime, &wasm) {
+                harness::record_artifact(&id, wasm.len() as u64, size);
+            }
+            group.bench_function(BenchmarkId::new(runtime.name(), wasm.len()), |b| {
+                dispatch::run(&case, &wasm, &mut Bench(b))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn compile_scaling<M: Measurement>(c: &mut Criterion<M>) {
+    std::fs::create_dir_all("./tmp/lucet").unwrap();
+    for &dimension in Dimension::ALL {
+        bench_dimension(c, dimension);
+    }
+}
+
+measured_group!(benches, compile_scaling);
+criterion_main!(benches);
@@ -0,0 +1,72 @@
+use criterion::measurement::Measurement;
+use criterion::*;
+use std::time::Instant;
+use support::Bench;
+use wasm_runtime_benchmark::dispatch::Timer;
+use wasm_runtime_benchmark::harness::Budget;
+use wasm_runtime_benchmark::lucet_runner::{self, Pool, Shared};
+use wasm_runtime_benchmark::matrix::{Phase, Runtime, Selection};
+use wasm_runtime_benchmark::samples;
+
+#[macro_use]
+mod support;
+
+// Slots of the pooled region, more than one instance ever takes
+const SLOTS: usize = 16;
+
+// One group per sample, `lucet_pool/sample`, comparing ways to get a Lucet
+// instance: `fresh` loads the module and creates a region of one slot for every
+// instance as the instantiate phase does, `region` takes a slot of a region made
+// up front and `pooled` hands out an instance of a `Pool` and takes it back.
+// Samples of the instantiate phase only, admitted through the budget with a
+// compile and a fresh instance under the timeout, with `lucet_pool/sample` as id.
+fn lucet_pool<M: Measurement>(c: &mut Criterion<M>) {
+    let budget = Budget::from_env();
+    let selection = Selection::from_env().expect("invalid selection");
+    if !selection.runtimes().contains(&Runtime::Lucet) {
+        return;
+    }
+    std::fs::create_dir_all("./tmp/lucet").unwrap();
+
+    let samples = samples::load_default().expect("invalid samples");
+    let samples = samples
+        .iter()
+        .filter(|s| s.in_phase(Phase::Instantiate) && selection.has_sample(s));
+    for sample in samples {
+        let id = format!("lucet_pool/{}", sample.name);
+        let probe = {
+            let wasm = sample.wasm.clone();
+            move || {
+                let moduleid = lucet_runner::compile(&wasm);
+                let start = Instant::now();
+                black_box(lucet_runner::instantiate(&moduleid));
+                start.elapsed()
+            }
+        };
+        if !budget.admit(&id, probe) {
+            continue;
+        }
+
+        let moduleid = lucet_runner::compile(&sample.wasm);
+        let mut group = support::group(c, id);
+        group.sample_size(10);
+
+        group.bench_function("fresh", |b| {
+            Bench(b).iter(|| lucet_runner::instantiate(&moduleid))
+        });
+        let shared = Shared::new(&moduleid, SLOTS);
+        group.bench_function("region", |b| Bench(b).iter(|| shared.instantiate()));
+        let mut pool = Pool::new(&moduleid, SLOTS);
+        group.bench_function("pooled", |b| {
+            Bench(b).iter(|| {
+                let instance = pool.take();
+                pool.give(black_box(instance));
+            })
+        });
+
+        group.finish();
+    }
+}
+
+measured_group!(benches, lucet_pool);
+criterion_main!(benches);
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use wasm_runtime_benchmark::dispatch;
+use wasm_runtime_benchmark::harness::{run_with_timeout, Budget, Outcome, Plan};
+use wasm_runtime_benchmark::matrix::{Matrix, Phase, Selection};
+use wasm_runtime_benchmark::memory::{self, CountingAlloc, Footprint};
+use wasm_runtime_benchmark::samples;
+use wasm_runtime_benchmark::table::Table;
+use wasm_runtime_benchmark::{host, isolation};
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+const PHASES: &[Phase] = &[
+    Phase::Compile,
+    Phase::Instantiate,
+    Phase::Execute,
+    Phase::AotCompile,
+];
+
+// Prints a table per phase of the peak RSS and peak heap each runtime took, with
+// the same selection and budget as the time benchmarks. Stops at the first case
+// which times out, its thread keeps running and would show up in the
+// process-wide numbers of every case after it.
+fn main() {
+    let budget = Budget::from_env();
+    let samples = samples::load_default().expect("invalid samples");
+    let matrix = Matrix::new(&samples, Selection::from_env().expect("invalid selection"));
+    let runtimes = matrix.selection.runtimes();
+    std::fs::create_dir_all("./tmp/lucet").unwrap();
+    host::record(isolation::apply());
+    let mut timed_out = None;
+
+    for phase in matrix.selection.phases() {
+        if !PHASES.contains(&phase) {
+            continue;
+        }
+        let cases: Vec<_> = matrix
+            .cases(phase)
+            .into_iter()
+            .filter(dispatch::supported)
+            .collect();
+        let columns: Vec<_> = runtimes
+            .iter()
+            .filter(|r| cases.iter().any(|c| c.runtime == **r))
+            .collect();
+
+        let mut header = vec![format!("{} (peak RSS / heap)", phase)];
+        header.extend(columns.iter().map(|r| r.to_string()));
+        let mut table = Table::new(&header);
+
+        let mut rows: Vec<(String, String)> = vec![];
+        for case in &cases {
+            let row = (case.sample.clone(), case.input());
+            if !rows.contains(&row) {
+                rows.push(row);
+            }
+        }
+
+        for (sample, input) in rows {
+            let mut cells = vec![format!("{} ({})", sample, input)];
+            for &runtime in &columns {
+                let case = cases
+                    .iter()
+                    .find(|c| c.sample == sample && c.input() == input && c.runtime == *runtime);
+                let case = match case {
+                    Some(_) if timed_out.is_some() => {
+                        cells.push("not run".to_owned());
+                        continue;
+                    }
+                    Some(case) if budget.plan(&case.id()) != Plan::Skip => case.clone(),
+                    Some(_) => {
+                        cells.push("skipped".to_owned());
+                        continue;
+                    }
+                    None => {
+                        cells.push("NA".to_owned());
+                        continue;
+                    }
+                };
+
+                let id = case.id();
+                let wasm = Arc::new(matrix.sample(&sample).unwrap().wasm.clone());
+                let outcome = run_with_timeout(budget.timeout, move || {
+                    let mut footprint = Footprint::default();
+                    dispatch::run(&case, &wasm, &mut footprint);
+                    footprint.0
+                });
+                cells.push(match outcome {
+                    Outcome::Finished(usage, _) => {
+                        memory::record(&id, &usage);
+                        usage.to_string()
+                    }
+                    Outcome::TimedOut(after) => {
+                        timed_out = Some(id.clone());
+                        format!("timed out after {:?}", after)
+                    }
+                    Outcome::Failed(message) => {
+                        eprintln!("{}: {}", id, message);
+                        "failed".to_owned()
+                    }
+                });
+            }
+            table.row(&cells);
+        }
+
+        if !table.is_empty() {
+            println!("{}", table.render());
+        }
+    }
+
+    if let Some(id) = timed_out {
+        println!(
+            "stopped after {} timed out, the cases after it were not run",
+            id
+        );
+    }
+}
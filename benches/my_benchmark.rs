@@ -1,405 +1,114 @@
+use criterion::measurement::Measurement;
 use criterion::*;
-use fibonacci;
 use lazy_static::lazy_static;
-use nbody;
-use wasm_runtime_benchmark::{lucet_runner, wasmer_runner};
+use support::Bench;
+use wasm_runtime_benchmark::dispatch::{self, Once};
+use wasm_runtime_benchmark::harness::{self, Budget};
+use wasm_runtime_benchmark::matrix::{Case, Matrix, Phase, Selection};
+use wasm_runtime_benchmark::samples::{self, Sample};
 
-use std::collections::HashMap;
+#[macro_use]
+mod support;
 
 lazy_static! {
-    static ref SAMPLES: HashMap<&'static str, &'static [u8]> = {
-        let mut map = HashMap::new();
-        map.insert(
-            "add-one",
-            &include_bytes!("../wasm-sample/add-one.wasm")[..],
-        );
-        map.insert(
-            "fibonacci",
-            &include_bytes!("../wasm-sample/fibonacci.wasm")[..],
-        );
-        map.insert(
-            "nbody",
-            &include_bytes!("../wasm-sample/nbody.wasm")[..],
-        );
-        // Too slow to compile
-        // map.insert(
-        //     "mruby-script",
-        //     &include_bytes!("../wasm-sample/mruby-script.wasm")[..],
-        // );
-        map
-    };
+    static ref BUDGET: Budget = Budget::from_env();
+    static ref SAMPLES: Vec<Sample> = samples::load_default().expect("invalid samples");
+    static ref MATRIX: Matrix<'static> =
+        Matrix::new(&SAMPLES, Selection::from_env().expect("invalid selection"));
 }
 
-static WASM: &'static [u8] = include_bytes!("../wasm-sample/fibonacci.wasm");
-
-fn jit(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("jit");
-
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-singlepass"),
-            wasm,
-            |b, &wasm| {
-                b.iter(|| {
-                    let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                    wrapper.jit(&wasm, black_box(10))
-                });
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-cranelift"),
-            wasm,
-            |b, &wasm| {
-                b.iter(|| {
-                    let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                    wrapper.jit(&wasm, black_box(10))
-                });
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-llvm"),
-            wasm,
-            |b, &wasm| {
-                b.iter(|| {
-                    let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                    wrapper.jit(&wasm, black_box(10))
-                });
-            },
-        );
-
-        group.finish();
-    }
+// Admits the case through the budget with a single timed run
+fn admit(case: &Case, sample: &'static Sample) -> bool {
+    let probe = {
+        let case = case.clone();
+        move || {
+            let mut timer = Once::default();
+            dispatch::run(&case, &sample.wasm, &mut timer);
+            timer.0
+        }
+    };
+    BUDGET.admit(&case.id(), probe)
 }
 
-fn aot_compile(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("aot_compile");
-
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-singlepass"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                b.iter(|| black_box(wrapper.aot_c(&wasm).unwrap()))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-cranelift"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                b.iter(|| black_box(wrapper.aot_c(&wasm).unwrap()))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-llvm"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                b.iter(|| black_box(wrapper.aot_c(&wasm)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "lucet"),
-            wasm,
-            |b, &wasm| b.iter(|| black_box(lucet_runner::aot_c(&wasm))),
-        );
-
-        group.finish();
-    }
+fn supported_cases(phase: Phase) -> Vec<Case> {
+    MATRIX
+        .cases(phase)
+        .into_iter()
+        .filter(dispatch::supported)
+        .collect()
 }
 
-fn aot_execute(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("aot_execute");
-
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-singlepass"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                let key = wrapper.aot_c(&wasm).unwrap();
-                b.iter(|| wrapper.aot_e(&key, black_box(10)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-cranelift"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                let key = wrapper.aot_c(&wasm).unwrap();
-                b.iter(|| wrapper.aot_e(&key, black_box(10)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-llvm"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                let key = wrapper.aot_c(&wasm).unwrap();
-                b.iter(|| wrapper.aot_e(&key, black_box(10)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "lucet"),
-            wasm,
-            |b, &wasm| {
-                let moduleid = lucet_runner::aot_c(&wasm);
-                b.iter(|| lucet_runner::aot_e(&moduleid, black_box(10)))
-            },
-        );
-
-        group.finish();
+// One group per phase, with ids as `phase/sample/runtime/args`. AOT compiles also
+// record the size of the artifact.
+fn bench_phase<M: Measurement>(c: &mut Criterion<M>, phase: Phase) {
+    let cases = supported_cases(phase);
+    if cases.is_empty() {
+        return;
     }
-}
 
-fn aot_total(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("aot_total");
+    let mut group = support::group(c, phase.name().to_owned());
+    group.sample_size(10);
 
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-singlepass"),
-            wasm,
-            |b, &wasm| {
-                b.iter(|| {
-                    let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                    wrapper.aot_t(&wasm, black_box(10))
-                })
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-cranelift"),
-            wasm,
-            |b, &wasm| {
-                b.iter(|| {
-                    let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                    wrapper.aot_t(&wasm, black_box(10))
-                })
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-llvm"),
-            wasm,
-            |b, &wasm| {
-                b.iter(|| {
-                    let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                    wrapper.aot_t(&wasm, black_box(10))
-                })
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "lucet"),
-            wasm,
-            |b, &wasm| b.iter(|| lucet_runner::aot_t(&wasm, black_box(10))),
-        );
+    for case in cases {
+        let sample = MATRIX.sample(&case.sample).unwrap();
+        if !admit(&case, sample) {
+            continue;
+        }
+        if phase == Phase::AotCompile {
+            if let Some(size) = dispatch::artifact_size(case.runtime, &sample.wasm) {
+                harness::record_artifact(&case.id(), sample.wasm.len() as u64, size);
+            }
+        }
 
-        group.finish();
-    }
-}
-
-fn execute(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("execute");
-
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "rust-native"),
-            wasm,
-            |b, &wasm| match name {
-                &"add-one" => {
-                    fn add_one(n: u32) -> u32 {
-                        n + 1
-                    }
-                    b.iter(|| black_box(add_one(10)))
-                }
-                &"fibonacci" => b.iter(|| black_box(fibonacci::run(10))),
-                &"nbody" => b.iter(|| unsafe { black_box(nbody::run(10)) }),
-                _ => unreachable!(),
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-singlepass"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                let instance = wrapper.prepare(&wasm).unwrap();
-                b.iter(|| wrapper.execute(&instance, black_box(10)))
-            },
+        group.bench_function(
+            BenchmarkId::new(format!("{}/{}", case.sample, case.runtime), case.input()),
+            |b| dispatch::run(&case, &sample.wasm, &mut Bench(b)),
         );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-cranelift"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                let instance = wrapper.prepare(&wasm).unwrap();
-                b.iter(|| wrapper.execute(&instance, black_box(10)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "wasmer-llvm"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                let instance = wrapper.prepare(&wasm).unwrap();
-                b.iter(|| wrapper.execute(&instance, black_box(10)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "lucet"),
-            wasm,
-            |b, &wasm| {
-                let mut instance = lucet_runner::prepare(&wasm);
-                b.iter(|| lucet_runner::execute(&mut instance, 10))
-            },
-        );
-
-        group.finish();
     }
-}
 
-fn wasmer_singlepass(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("wasmer_singlepass");
-
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "compile"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                b.iter(|| black_box(wrapper.compile(&wasm)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "instantiate"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                let module = wrapper.compile(&wasm);
-                b.iter(|| black_box(wrapper.instantiate(&module)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "execute"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Singlepass);
-                let module = wrapper.compile(&wasm);
-                let instance = wrapper.instantiate(&module).unwrap();
-                b.iter(|| black_box(wrapper.execute(&instance, 10)))
-            },
-        );
-        group.finish();
-    }
+    group.finish();
 }
 
-fn wasmer_cranelift(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("wasmer_cranelift");
+// One group per sample, with the runtimes as functions over the sample's inputs,
+// so criterion plots each runtime against the input. The ids are the same as the
+// other phases', `execute/sample/runtime/input`.
+fn bench_execute<M: Measurement>(c: &mut Criterion<M>) {
+    let cases = supported_cases(Phase::Execute);
+
+    for sample in MATRIX.samples {
+        let cases: Vec<&Case> = cases.iter().filter(|c| c.sample == sample.name).collect();
+        if cases.is_empty() {
+            continue;
+        }
+
+        let mut group = support::group(c, format!("{}/{}", Phase::Execute, sample.name));
+        group.sample_size(10);
+
+        for case in cases {
+            if !admit(case, sample) {
+                continue;
+            }
+            if let (true, [input]) = (sample.linear, case.args.as_slice()) {
+                group.throughput(Throughput::Elements(u64::from(*input)));
+            }
+
+            group.bench_function(BenchmarkId::new(case.runtime.name(), case.input()), |b| {
+                dispatch::run(case, &sample.wasm, &mut Bench(b))
+            });
+        }
 
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "compile"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                b.iter(|| black_box(wrapper.compile(&wasm)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "instantiate"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                let module = wrapper.compile(&wasm);
-                b.iter(|| black_box(wrapper.instantiate(&module)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "execute"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::Cranelift);
-                let module = wrapper.compile(&wasm);
-                let instance = wrapper.instantiate(&module).unwrap();
-                b.iter(|| black_box(wrapper.execute(&instance, 10)))
-            },
-        );
-        group.finish();
-    }
-}
-
-fn wasmer_llvm(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("wasmer_llvm");
-
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "compile"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                b.iter(|| black_box(wrapper.compile(&wasm)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "instantiate"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                let module = wrapper.compile(&wasm);
-                b.iter(|| black_box(wrapper.instantiate(&module)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "execute"),
-            wasm,
-            |b, &wasm| {
-                let wrapper = wasmer_runner::Wrapper::new(wasmer_runtime::Backend::LLVM);
-                let module = wrapper.compile(&wasm);
-                let instance = wrapper.instantiate(&module).unwrap();
-                b.iter(|| black_box(wrapper.execute(&instance, 10)))
-            },
-        );
         group.finish();
     }
 }
 
-fn lucet(c: &mut Criterion) {
-    for (name, wasm) in SAMPLES.iter() {
-        let mut group = c.benchmark_group("lucet");
-
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "compile"),
-            wasm,
-            |b, &wasm| b.iter(|| black_box(lucet_runner::compile(&wasm))),
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "instantiate"),
-            wasm,
-            |b, &wasm| {
-                let moduleid = lucet_runner::compile(&wasm);
-                b.iter(|| black_box(lucet_runner::instantiate(&moduleid)))
-            },
-        );
-        group.sample_size(10).bench_with_input(
-            BenchmarkId::new(name.to_owned(), "execute"),
-            wasm,
-            |b, &wasm| {
-                let moduleid = lucet_runner::compile(&wasm);
-                let mut instance = lucet_runner::instantiate(&moduleid);
-                b.iter(|| black_box(lucet_runner::execute(&mut instance, 10)))
-            },
-        );
-        group.finish();
+fn matrix<M: Measurement>(c: &mut Criterion<M>) {
+    for phase in MATRIX.selection.phases() {
+        match phase {
+            Phase::Execute => bench_execute(c),
+            _ => bench_phase(c, phase),
+        }
     }
 }
 
-criterion_group!(
-    benches, // jit,
-    // aot_compile,
-    // aot_execute,
-    // aot_total,
-    // execute,
-    // wasmer_singlepass,
-    // wasmer_cranelift,
-    // wasmer_llvm,
-    lucet
-);
+measured_group!(benches, matrix);
 criterion_main!(benches);
@@ -0,0 +1,265 @@
+// Shared by the criterion benchmarks: the `Timer` adapter and the measurements a
+// run can be switched to with BENCH_MEASUREMENT
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{Bencher, BenchmarkGroup, Criterion, Throughput};
+use std::time::Duration;
+use wasm_runtime_benchmark::cpu;
+use wasm_runtime_benchmark::dispatch::Timer;
+use wasm_runtime_benchmark::export;
+use wasm_runtime_benchmark::perf::{Counter, Event};
+
+pub struct Bench<'a, 'b, M: Measurement>(pub &'a mut Bencher<'b, M>);
+
+impl<M: Measurement> Timer for Bench<'_, '_, M> {
+    fn iter<O, R: FnMut() -> O>(&mut self, routine: R) {
+        self.0.iter(routine)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Wall,
+    ThreadCpu,
+    ProcessCpu,
+    Perf(Event),
+}
+
+impl Metric {
+    // As given in BENCH_MEASUREMENT
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Wall => "wall",
+            Metric::ThreadCpu => "thread-cpu",
+            Metric::ProcessCpu => "process-cpu",
+            Metric::Perf(event) => event.name(),
+        }
+    }
+}
+
+// BENCH_MEASUREMENT is one of `wall` (default), `thread-cpu`, `process-cpu` or a
+// software event like `minor-faults`
+pub fn metric() -> Metric {
+    match std::env::var("BENCH_MEASUREMENT").as_deref() {
+        Err(_) | Ok("") | Ok("wall") => Metric::Wall,
+        Ok("thread-cpu") => Metric::ThreadCpu,
+        Ok("process-cpu") => Metric::ProcessCpu,
+        Ok(other) => match other.parse() {
+            Ok(event) => Metric::Perf(event),
+            Err(_) => panic!("unknown BENCH_MEASUREMENT `{}`", other),
+        },
+    }
+}
+
+// A criterion group which records the BENCH_MEASUREMENT it is measured with
+pub fn group<M: Measurement>(c: &mut Criterion<M>, id: String) -> BenchmarkGroup<'_, M> {
+    export::record_measurement(&id, metric().name());
+    c.benchmark_group(id)
+}
+
+// Defines a criterion group whose targets are generic over the measurement,
+// run with the one BENCH_MEASUREMENT selects, after applying BENCH_CPU and
+// BENCH_NICE and recording the host
+macro_rules! measured_group {
+    ($name:ident, $( $target:path ),+ $(,)*) => {
+        pub fn $name() {
+            wasm_runtime_benchmark::host::record(wasm_runtime_benchmark::isolation::apply());
+            let metric = support::metric();
+            match metric {
+                support::Metric::Wall => {
+                    let mut c = Criterion::default().configure_from_args();
+                    $( $target(&mut c); )+
+                }
+                support::Metric::ThreadCpu => {
+                    let mut c = Criterion::default()
+                        .with_measurement(support::CpuTime::Thread)
+                        .configure_from_args();
+                    $( $target(&mut c); )+
+                }
+                support::Metric::ProcessCpu => {
+                    let mut c = Criterion::default()
+                        .with_measurement(support::CpuTime::Process)
+                        .configure_from_args();
+                    $( $target(&mut c); )+
+                }
+                // Counts are often the same in every sample, which the plots
+                // hang on. `configure_from_args` turns them back on.
+                support::Metric::Perf(event) => {
+                    let mut c = Criterion::default()
+                        .with_measurement(support::PerfCounter::open(event))
+                        .configure_from_args()
+                        .without_plots();
+                    $( $target(&mut c); )+
+                }
+            }
+        }
+    };
+}
+
+// CPU time instead of wall-clock time. `Thread` only counts the benchmark's own
+// thread, `Process` adds the background threads of the runtime, eg. for
+// parallel compilation. Neither counts time spent waiting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuTime {
+    Thread,
+    Process,
+}
+
+impl CpuTime {
+    fn now(self) -> Duration {
+        match self {
+            CpuTime::Thread => cpu::thread_time(),
+            CpuTime::Process => cpu::process_time(),
+        }
+    }
+}
+
+impl Measurement for CpuTime {
+    type Intermediate = Duration;
+    type Value = Duration;
+
+    fn start(&self) -> Self::Intermediate {
+        self.now()
+    }
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        self.now() - start
+    }
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        *v1 + *v2
+    }
+    fn zero(&self) -> Self::Value {
+        Duration::from_secs(0)
+    }
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        value.as_nanos() as f64
+    }
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &NanosFormatter
+    }
+}
+
+// Same units as criterion's formatter for wall-clock time, which isn't public
+pub struct NanosFormatter;
+
+impl ValueFormatter for NanosFormatter {
+    fn scale_values(&self, ns: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = if ns < 1.0 {
+            (1e3, "ps")
+        } else if ns < 1e3 {
+            (1.0, "ns")
+        } else if ns < 1e6 {
+            (1e-3, "us")
+        } else if ns < 1e9 {
+            (1e-6, "ms")
+        } else {
+            (1e-9, "s")
+        };
+        for value in values {
+            *value *= factor;
+        }
+        unit
+    }
+
+    fn scale_throughputs(
+        &self,
+        typical: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (per_iteration, base, units) = match *throughput {
+            Throughput::Bytes(n) => (n as f64, 1024f64, ["B/s", "KiB/s", "MiB/s", "GiB/s"]),
+            Throughput::Elements(n) => (
+                n as f64,
+                1000f64,
+                ["elem/s", "Kelem/s", "Melem/s", "Gelem/s"],
+            ),
+        };
+
+        let per_second = per_iteration * 1e9 / typical;
+        let mut scale = 0;
+        while scale < units.len() - 1 && per_second >= base.powi(scale as i32 + 1) {
+            scale += 1;
+        }
+        for value in values {
+            *value = per_iteration * 1e9 / *value / base.powi(scale as i32);
+        }
+        units[scale]
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "ns"
+    }
+}
+
+// Counts of a software event per iteration, eg. page faults
+pub struct PerfCounter {
+    counter: Counter,
+    formatter: CountFormatter,
+}
+
+impl PerfCounter {
+    pub fn open(event: Event) -> Self {
+        let counter = Counter::open(event).unwrap_or_else(|e| {
+            panic!(
+                "cannot count {}: {}, check /proc/sys/kernel/perf_event_paranoid",
+                event, e
+            )
+        });
+        Self {
+            counter,
+            formatter: CountFormatter(event.name()),
+        }
+    }
+}
+
+impl Measurement for PerfCounter {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> Self::Intermediate {
+        self.counter.read()
+    }
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        self.counter.read() - start
+    }
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+    fn zero(&self) -> Self::Value {
+        0
+    }
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &self.formatter
+    }
+}
+
+// Plain counts named after the event, per byte or element for throughput
+pub struct CountFormatter(&'static str);
+
+impl ValueFormatter for CountFormatter {
+    fn scale_values(&self, _typical: f64, _values: &mut [f64]) -> &'static str {
+        self.0
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (per_iteration, unit) = match *throughput {
+            Throughput::Bytes(n) => (n, "per byte"),
+            Throughput::Elements(n) => (n, "per element"),
+        };
+        for value in values {
+            *value /= per_iteration as f64;
+        }
+        unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        self.0
+    }
+}
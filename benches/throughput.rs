@@ -0,0 +1,112 @@
+use std::time::Duration;
+use wasm_runtime_benchmark::harness::{run_with_timeout, wait_for_leftover, Budget, Outcome, Plan};
+use wasm_runtime_benchmark::matrix::{Phase, Runtime, Selection};
+use wasm_runtime_benchmark::samples;
+use wasm_runtime_benchmark::table::{format_time, Table};
+use wasm_runtime_benchmark::throughput::{self, default_threads};
+use wasm_runtime_benchmark::{host, isolation};
+
+const DEFAULT_SECS: f64 = 2.0;
+
+// BENCH_THREADS           comma separated thread counts, 1, 2, 4, ... up to the
+//                         number of cores by default
+// BENCH_THROUGHPUT_SECS   how long each thread count serves requests, 2 by default
+fn config() -> (Vec<usize>, Duration) {
+    let threads = std::env::var("BENCH_THREADS")
+        .map(|v| {
+            v.split(',')
+                .map(|n| n.trim().parse().expect("invalid BENCH_THREADS"))
+                .filter(|n| *n > 0)
+                .collect()
+        })
+        .unwrap_or_else(|_| default_threads());
+    let secs = std::env::var("BENCH_THROUGHPUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_SECS);
+    (threads, Duration::from_secs_f64(secs))
+}
+
+// Prints a table per sample of the requests per second and the p50 and p99
+// latency of each runtime as the number of threads sharing one compiled module
+// grows, a request being a fresh instance and a call. Samples of the execute
+// phase only, with the same selection and budget as the time benchmarks, the
+// budget on top of the time spent serving. Slow combinations serve
+// BENCH_SLOW_ITERS requests per thread.
+fn main() {
+    let budget = Budget::from_env();
+    let samples = samples::load_default().expect("invalid samples");
+    let selection = Selection::from_env().expect("invalid selection");
+    let (threads, duration) = config();
+    std::fs::create_dir_all("./tmp/lucet").unwrap();
+    host::record(isolation::apply());
+
+    let runtimes: Vec<Runtime> = selection
+        .runtimes()
+        .into_iter()
+        .filter(|r| Phase::Instantiate.supports(*r))
+        .collect();
+    let samples = samples
+        .iter()
+        .filter(|s| s.in_phase(Phase::Execute) && selection.has_sample(s));
+    for sample in samples {
+        let mut table = Table::new(&[
+            format!("{} ({:?})", sample.name, sample.args),
+            "threads".to_owned(),
+            "requests/s".to_owned(),
+            "p50".to_owned(),
+            "p99".to_owned(),
+        ]);
+
+        for &runtime in &runtimes {
+            let id = format!("throughput/{}/{}", sample.name, runtime);
+            let (limit, mark) = match budget.plan(&id) {
+                Plan::Skip => {
+                    table.row(&[runtime.to_string(), "skipped".to_owned()]);
+                    continue;
+                }
+                Plan::Reduced(iters) => (Some(iters as usize), "~"),
+                Plan::Run => (None, ""),
+            };
+            if !wait_for_leftover(budget.timeout) {
+                table.row(&[runtime.to_string(), "left over".to_owned()]);
+                continue;
+            }
+            let (wasm, export, args) = (
+                sample.wasm.clone(),
+                sample.export.clone(),
+                sample.args.clone(),
+            );
+            let counts = threads.clone();
+            let outcome = run_with_timeout(
+                budget.timeout + duration * threads.len() as u32,
+                move || throughput::serve(runtime, &wasm, &export, &args, &counts, duration, limit),
+            );
+
+            match outcome {
+                Outcome::Finished(results, _) => {
+                    for result in results {
+                        table.row(&[
+                            runtime.to_string(),
+                            result.threads.to_string(),
+                            format!("{}{:.0}", mark, result.per_second()),
+                            format!("{}{}", mark, format_time(result.p50.as_nanos() as f64)),
+                            format!("{}{}", mark, format_time(result.p99.as_nanos() as f64)),
+                        ]);
+                    }
+                }
+                Outcome::TimedOut(after) => {
+                    table.row(&[runtime.to_string(), format!("timed out after {:?}", after)])
+                }
+                Outcome::Failed(message) => {
+                    eprintln!("{}: {}", id, message);
+                    table.row(&[runtime.to_string(), "failed".to_owned()]);
+                }
+            }
+        }
+
+        if !table.is_empty() {
+            println!("{}", table.render());
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wasm_runtime_benchmark::harness::{run_with_timeout, wait_for_leftover, Budget, Outcome, Plan};
+use wasm_runtime_benchmark::latency;
+use wasm_runtime_benchmark::matrix::{Phase, Runtime, Selection};
+use wasm_runtime_benchmark::samples::{self, Sample};
+use wasm_runtime_benchmark::table::{format_time, Table};
+use wasm_runtime_benchmark::wasmer_runner::Wrapper;
+use wasm_runtime_benchmark::{host, isolation};
+use wasmer_runtime::Backend;
+
+const DEFAULT_CALLS: usize = 1000;
+
+// Tiers to move up to from Singlepass
+const OPTIMIZED: &[Runtime] = &[Runtime::WasmerCranelift, Runtime::WasmerLlvm];
+
+// Latency of each of `calls` calls, the first one including compiling and
+// instantiating, on `backend` or tiered from it to `optimized`. With the call
+// which went to the optimized tier first.
+fn latencies(
+    sample: &Sample,
+    backend: Backend,
+    optimized: Option<Backend>,
+    calls: usize,
+) -> (Vec<Duration>, Option<usize>) {
+    let wrapper = Wrapper::new(backend);
+    let mut latencies = Vec::with_capacity(calls);
+    let mut switched = None;
+    let mut last = Instant::now();
+
+    match optimized {
+        Some(optimized) => {
+            let mut tiered = wrapper.tiered(&sample.wasm, optimized).unwrap();
+            for i in 0..calls {
+                tiered.call(&sample.export, &sample.args).unwrap();
+                let now = Instant::now();
+                latencies.push(now - last);
+                last = now;
+                if switched.is_none() && tiered.backend() == optimized {
+                    switched = Some(i);
+                }
+            }
+        }
+        None => {
+            let instance = wrapper.prepare(&sample.wasm).unwrap();
+            for _ in 0..calls {
+                wrapper
+                    .call(&instance, &sample.export, &sample.args)
+                    .unwrap();
+                let now = Instant::now();
+                latencies.push(now - last);
+                last = now;
+            }
+        }
+    }
+    (latencies, switched)
+}
+
+// Prints a table per sample and higher tier of the mean latency over the first
+// BENCH_TIERED_CALLS calls (1000 by default) on Singlepass alone, tiered from
+// Singlepass to the higher tier and on the higher tier alone, timed from the
+// start of compiling. With the same selection and budget as the time
+// benchmarks: slow combinations only make a few calls, their cells read `~`
+// and decades they don't reach `/`.
+fn main() {
+    let budget = Budget::from_env();
+    let samples = samples::load_default().expect("invalid samples");
+    let selection = Selection::from_env().expect("invalid selection");
+    let calls = std::env::var("BENCH_TIERED_CALLS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CALLS)
+        .max(1);
+    host::record(isolation::apply());
+
+    let runtimes = selection.runtimes();
+    let samples = samples
+        .into_iter()
+        .filter(|s| s.in_phase(Phase::Execute) && selection.has_sample(s));
+    for sample in samples {
+        let sample = Arc::new(sample);
+        for optimized in OPTIMIZED.iter().filter(|r| runtimes.contains(r)) {
+            let backend = optimized.backend().unwrap();
+            // Header and id of each column, and the tier to move up to
+            let columns = [
+                (Runtime::WasmerSinglepass.to_string(), "singlepass", None),
+                (format!("tiered to {}", optimized), "tiered", Some(backend)),
+                (optimized.to_string(), "optimized", None),
+            ];
+
+            let mut header = vec![format!("{} (calls)", sample.name)];
+            header.extend(columns.iter().map(|(name, _, _)| name.clone()));
+            let mut rows: Vec<Vec<String>> = latency::decades(calls)
+                .iter()
+                .map(|range| vec![latency::label(range)])
+                .collect();
+            rows.push(vec!["total".to_owned()]);
+            let mut notes = vec![];
+
+            for (i, (name, column, tier)) in columns.iter().enumerate() {
+                let id = format!("tiered/{}/{}/{}", sample.name, optimized, column);
+                let start = if i == 2 { backend } else { Backend::Singlepass };
+                let (calls, mark) = match budget.plan(&id) {
+                    Plan::Skip => {
+                        rows.iter_mut()
+                            .for_each(|row| row.push("skipped".to_owned()));
+                        continue;
+                    }
+                    Plan::Reduced(iters) => (calls.min(iters as usize), "~"),
+                    Plan::Run => (calls, ""),
+                };
+                if !wait_for_leftover(budget.timeout) {
+                    rows.iter_mut()
+                        .for_each(|row| row.push("left over".to_owned()));
+                    continue;
+                }
+                let (sample, tier) = (sample.clone(), *tier);
+                let outcome = run_with_timeout(budget.timeout, move || {
+                    latencies(&sample, start, tier, calls)
+                });
+
+                let cell = match outcome {
+                    Outcome::Finished((latencies, switched), _) => {
+                        let time =
+                            |d: Duration| format!("{}{}", mark, format_time(d.as_nanos() as f64));
+                        let means = latency::by_decade(&latencies);
+                        let last = rows.len() - 1;
+                        let (decades, total_row) = rows.split_at_mut(last);
+                        for (i, row) in decades.iter_mut().enumerate() {
+                            row.push(
+                                means
+                                    .get(i)
+                                    .map_or_else(|| "/".to_owned(), |(_, mean)| time(*mean)),
+                            );
+                        }
+                        total_row[0].push(time(latencies.iter().sum()));
+
+                        if tier.is_some() {
+                            notes.push(match switched {
+                                Some(call) => {
+                                    let before: Duration = latencies[..call].iter().sum();
+                                    format!(
+                                        "{} switched at call {}, after {}",
+                                        name,
+                                        call + 1,
+                                        format_time(before.as_nanos() as f64)
+                                    )
+                                }
+                                None => format!("{} never switched", name),
+                            });
+                        }
+                        continue;
+                    }
+                    Outcome::TimedOut(after) => format!("timed out after {:?}", after),
+                    Outcome::Failed(message) => {
+                        eprintln!("{}: {}", id, message);
+                        "failed".to_owned()
+                    }
+                };
+                rows.iter_mut().for_each(|row| row.push(cell.clone()));
+            }
+
+            let mut table = Table::new(&header);
+            for row in &rows {
+                table.row(row);
+            }
+            println!("{}", table.render());
+            for note in notes {
+                println!("{}", note);
+            }
+            println!();
+        }
+    }
+}
@@ -0,0 +1,895 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use wasm_runtime_benchmark::cold;
+use wasm_runtime_benchmark::density::{self, Limits};
+use wasm_runtime_benchmark::dispatch::{self, Loop};
+use wasm_runtime_benchmark::export::{format_mean, Results};
+use wasm_runtime_benchmark::harness::{
+    output_dir, run_with_timeout, target_dir, wait_for_leftover, Outcome,
+};
+use wasm_runtime_benchmark::history::{
+    self, built_versions, changed_samples, history_dir, sample_digests, Meta, Run, Verdict,
+};
+use wasm_runtime_benchmark::isolation;
+use wasm_runtime_benchmark::matrix::{Case, Phase, Runtime};
+use wasm_runtime_benchmark::memory::{Footprint, Usage};
+use wasm_runtime_benchmark::report::Report;
+use wasm_runtime_benchmark::samples::{self, Sample};
+use wasm_runtime_benchmark::table::{format_size, format_time, Table};
+use wasm_runtime_benchmark::trap::{self, Handled};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "wasm-bench", about = "Benchmark WebAssembly runtimes")]
+enum Command {
+    /// Run the validate/compile/instantiate/execute/AOT phases of a module on each runtime
+    Run(RunOpts),
+    /// List the samples in the catalogue
+    Samples,
+    /// Time AOT cold starts, each in a freshly spawned process which loads the
+    /// compiled module, instantiates it and calls the export once
+    ColdStart(ColdStartOpts),
+    /// Keep instantiating a module on each runtime until a memory budget, a
+    /// limit or a failure, and report how many instances fit and their memory
+    Density(DensityOpts),
+    /// Call the trapping samples of wasm-sample/traps on each runtime, in a
+    /// spawned process per sample and runtime, and report the kind of trap, how
+    /// long the error took to come back and whether the instance still works
+    Traps(TrapsOpts),
+    /// Gather criterion's estimates, outcomes, artifact sizes and memory usage
+    /// into target/wasm-bench/results.json and results.csv
+    Export {
+        /// Criterion baseline to read, `new` is the last run
+        #[structopt(short, long, default_value = "new")]
+        baseline: String,
+
+        /// Criterion's output directory, target/criterion by default
+        #[structopt(long)]
+        criterion_dir: Option<PathBuf>,
+    },
+    /// Render the README's tables from target/wasm-bench/results.json
+    Report {
+        /// Update the tables below the `<!-- report: name -->` markers of this
+        /// markdown file instead of printing them all
+        #[structopt(long)]
+        readme: Option<PathBuf>,
+        /// Also write charts of the results to target/wasm-bench/report.html
+        #[structopt(long)]
+        html: bool,
+    },
+    /// Save target/wasm-bench/results.json to the history, tagged with the git
+    /// commit and the runtimes' versions
+    Save {
+        /// Name of the saved run, `git describe` by default
+        name: Option<String>,
+    },
+    /// List the saved runs
+    History,
+    /// Compare results against a saved run, failing on regressions
+    Compare(CompareOpts),
+    /// Run by `cold-start` in the spawned process
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    ColdStartChild {
+        runtime: Runtime,
+        /// Artifact key, or the native implementation for rust-native
+        key: String,
+        export: String,
+        args: Vec<u32>,
+    },
+    /// Run by `traps` in the spawned process
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    TrapChild {
+        runtime: Runtime,
+        sample: String,
+        iterations: u32,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+struct RunOpts {
+    /// Path to a .wasm or .wat module, or the name of a sample in the catalogue
+    module: String,
+
+    /// Exported function to call, `run` or the sample's export by default
+    #[structopt(short, long)]
+    export: Option<String>,
+
+    /// Comma separated i32 arguments passed to the export, `10` or the sample's
+    /// arguments by default
+    #[structopt(short, long, use_delimiter = true)]
+    args: Vec<u32>,
+
+    /// Comma separated runtimes, all of them by default
+    #[structopt(short, long, use_delimiter = true)]
+    runtimes: Vec<Runtime>,
+
+    /// Comma separated phases, all of them by default
+    #[structopt(short, long, use_delimiter = true)]
+    phases: Vec<Phase>,
+
+    /// Timed iterations per phase, after one warm up iteration
+    #[structopt(short = "n", long, default_value = "10")]
+    iterations: u32,
+
+    /// Seconds a runtime may spend on a phase before it is given up
+    #[structopt(long, default_value = "120")]
+    timeout: f64,
+
+    /// Also print the peak RSS each phase takes. The heap isn't counted here,
+    /// as that needs a counting allocator under every timing too; `cargo bench
+    /// --bench memory` has both.
+    #[structopt(short, long)]
+    memory: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct ColdStartOpts {
+    /// Path to a .wasm or .wat module, or the name of a sample in the catalogue
+    module: String,
+
+    /// Exported function to call, `run` or the sample's export by default
+    #[structopt(short, long)]
+    export: Option<String>,
+
+    /// Comma separated i32 arguments passed to the export
+    #[structopt(short, long, use_delimiter = true)]
+    args: Vec<u32>,
+
+    /// Comma separated runtimes, all of them by default. rust-native starts a
+    /// process which calls the native implementation, the baseline for process
+    /// start up
+    #[structopt(short, long, use_delimiter = true)]
+    runtimes: Vec<Runtime>,
+
+    /// Processes to start per runtime
+    #[structopt(short = "n", long, default_value = "10")]
+    runs: u32,
+
+    /// Seconds a process may take before it is killed
+    #[structopt(long, default_value = "120")]
+    timeout: f64,
+
+    /// Drop the page cache before each start, needs root
+    #[structopt(long)]
+    drop_caches: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct DensityOpts {
+    /// Path to a .wasm or .wat module, or the name of a sample in the catalogue
+    module: String,
+
+    /// Exported function `--call` calls, `run` or the sample's export by default
+    #[structopt(short, long)]
+    export: Option<String>,
+
+    /// Comma separated i32 arguments passed to the export
+    #[structopt(short, long, use_delimiter = true)]
+    args: Vec<u32>,
+
+    /// Comma separated runtimes, all of them but rust-native by default
+    #[structopt(short, long, use_delimiter = true)]
+    runtimes: Vec<Runtime>,
+
+    /// MiB the RSS may grow by
+    #[structopt(long, default_value = "1024")]
+    max_rss: u64,
+
+    /// Instances to stop at
+    #[structopt(long, default_value = "100000")]
+    max_instances: usize,
+
+    /// Call the export once on every instance, so the memory it touches counts
+    #[structopt(long)]
+    call: bool,
+
+    /// Seconds a runtime may take before it is given up
+    #[structopt(long, default_value = "120")]
+    timeout: f64,
+}
+
+#[derive(Debug, StructOpt)]
+struct TrapsOpts {
+    /// Trap samples to call, all of them by default
+    samples: Vec<String>,
+
+    /// Comma separated runtimes, all of them but rust-native by default
+    #[structopt(short, long, use_delimiter = true)]
+    runtimes: Vec<Runtime>,
+
+    /// Trapping calls per runtime, each on a fresh instance
+    #[structopt(short = "n", long, default_value = "100")]
+    iterations: u32,
+
+    /// Seconds a process may take before it is killed
+    #[structopt(long, default_value = "120")]
+    timeout: f64,
+}
+
+#[derive(Debug, StructOpt)]
+struct CompareOpts {
+    /// Saved run to compare against
+    baseline: String,
+
+    /// Saved run to compare, target/wasm-bench/results.json by default
+    #[structopt(long)]
+    against: Option<String>,
+
+    /// Change of the mean in percent which counts as a regression or
+    /// improvement, if the confidence intervals don't overlap either
+    #[structopt(short, long, default_value = "5")]
+    threshold: f64,
+}
+
+// A module given by path, or by name from the sample catalogue
+fn resolve(module: &str) -> Result<Sample, String> {
+    let path = Path::new(module);
+    if !path.exists() {
+        return samples::load_default()?
+            .into_iter()
+            .find(|s| s.name == module)
+            .ok_or_else(|| format!("{}: no such file or sample", module));
+    }
+
+    let wasm = samples::read_module(path)?;
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Sample::new(&name, &wasm))
+}
+
+fn run(opts: RunOpts) -> Result<(), String> {
+    let mut sample = resolve(&opts.module)?;
+    if let Some(export) = &opts.export {
+        sample.export = export.clone();
+    }
+    if !opts.args.is_empty() {
+        sample.args = opts.args.clone();
+    }
+    // Both runners keep their AOT artifacts in here
+    std::fs::create_dir_all("./tmp/lucet").map_err(|e| e.to_string())?;
+    isolation::apply();
+
+    let runtimes = if opts.runtimes.is_empty() {
+        Runtime::ALL
+            .iter()
+            .copied()
+            .filter(|r| *r != Runtime::Native || sample.native.is_some())
+            .collect()
+    } else {
+        opts.runtimes.clone()
+    };
+    let phases = if opts.phases.is_empty() {
+        Phase::ALL.to_vec()
+    } else {
+        opts.phases.clone()
+    };
+
+    println!(
+        "{} ({}), calling `{}` with {:?}\n",
+        opts.module,
+        format_size(sample.wasm.len() as u64),
+        sample.export,
+        sample.args
+    );
+    let wasm = Arc::new(sample.wasm.clone());
+
+    let mut header = vec![String::new()];
+    header.extend(phases.iter().map(|p| p.to_string()));
+    let mut table = Table::new(&header);
+    header[0] = "peak RSS".to_owned();
+    let mut memory_table = Table::new(&header);
+    let timeout = Duration::from_secs_f64(opts.timeout);
+    let mut left_over = false;
+
+    for &runtime in &runtimes {
+        let mut row = vec![runtime.to_string()];
+        let mut memory_row = row.clone();
+        for &phase in &phases {
+            let case = Case {
+                phase,
+                sample: sample.name.clone(),
+                runtime,
+                export: sample.export.clone(),
+                args: sample.args.clone(),
+                native: sample.native.clone(),
+            };
+            if !dispatch::supported(&case) {
+                row.push("NA".to_owned());
+                memory_row.push("NA".to_owned());
+                continue;
+            }
+            // A timed out call keeps running, the cells after it wait for it
+            // and aren't run alongside it
+            if !wait_for_leftover(timeout) {
+                left_over = true;
+                row.push("left over".to_owned());
+                memory_row.push("left over".to_owned());
+                continue;
+            }
+
+            let iterations = opts.iterations;
+            let outcome = run_with_timeout(timeout, {
+                let (case, wasm) = (case.clone(), wasm.clone());
+                move || {
+                    let mut timer = Loop::new(iterations);
+                    dispatch::run(&case, &wasm, &mut timer);
+                    timer.mean()
+                }
+            });
+            row.push(cell(outcome, runtime, phase, |mean| {
+                format_time(mean.as_nanos() as f64)
+            }));
+
+            if opts.memory && !wait_for_leftover(timeout) {
+                left_over = true;
+                memory_row.push("left over".to_owned());
+            } else if opts.memory {
+                let wasm = wasm.clone();
+                let outcome = run_with_timeout(timeout, move || {
+                    let mut footprint = Footprint::default();
+                    dispatch::run(&case, &wasm, &mut footprint);
+                    footprint.0
+                });
+                memory_row.push(cell(outcome, runtime, phase, |usage: Usage| {
+                    usage
+                        .peak_rss
+                        .map(format_size)
+                        .unwrap_or_else(|| "NA".to_owned())
+                }));
+            }
+        }
+        table.row(&row);
+        memory_table.row(&memory_row);
+    }
+
+    print!("{}", table.render());
+    if opts.memory {
+        print!("\n{}", memory_table.render());
+    }
+    if left_over {
+        println!("\nleft over: not run, a timed out call was still running");
+    }
+    Ok(())
+}
+
+fn cell<T, F: Fn(T) -> String>(
+    outcome: Outcome<T>,
+    runtime: Runtime,
+    phase: Phase,
+    format: F,
+) -> String {
+    match outcome {
+        Outcome::Finished(value, _) => format(value),
+        Outcome::TimedOut(after) => format!("timed out after {:?}", after),
+        Outcome::Failed(message) => {
+            eprintln!("{} {}: {}", runtime, phase, message);
+            "failed".to_owned()
+        }
+    }
+}
+
+fn list_samples() -> Result<(), String> {
+    let mut table = Table::new(&[
+        "", "size", "class", "export", "args", "expected", "native", "groups",
+    ]);
+    for sample in samples::load_default()? {
+        let groups: Vec<String> = sample.phases.iter().map(|p| p.to_string()).collect();
+        table.row(&[
+            sample.name.clone(),
+            format_size(sample.wasm.len() as u64),
+            sample.size.to_string(),
+            sample.export.clone(),
+            format!("{:?}", sample.args),
+            sample.expected.map(|v| v.to_string()).unwrap_or_default(),
+            sample.native.clone().unwrap_or_default(),
+            if groups.is_empty() {
+                "all".to_owned()
+            } else {
+                groups.join(",")
+            },
+        ]);
+    }
+    print!("{}", table.render());
+    Ok(())
+}
+
+fn cold_start(opts: ColdStartOpts) -> Result<(), String> {
+    let mut sample = resolve(&opts.module)?;
+    if let Some(export) = &opts.export {
+        sample.export = export.clone();
+    }
+    if !opts.args.is_empty() {
+        sample.args = opts.args.clone();
+    }
+    std::fs::create_dir_all("./tmp/lucet").map_err(|e| e.to_string())?;
+    // Spawned processes inherit the affinity and nice value
+    isolation::apply();
+
+    let runtimes = if opts.runtimes.is_empty() {
+        Runtime::ALL
+            .iter()
+            .copied()
+            .filter(|r| Phase::AotExecute.supports(*r) || sample.native.is_some())
+            .collect()
+    } else {
+        opts.runtimes.clone()
+    };
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let timeout = Duration::from_secs_f64(opts.timeout);
+    let mut drop_caches = opts.drop_caches;
+
+    println!(
+        "{} ({}), calling `{}` with {:?} in {} fresh processes\n",
+        opts.module,
+        format_size(sample.wasm.len() as u64),
+        sample.export,
+        sample.args,
+        opts.runs
+    );
+    let mut table = Table::new(&["", "mean", "min", "max", "in process", "result"]);
+
+    for runtime in runtimes {
+        // A compile which timed out for the last runtime would still be
+        // running alongside the children
+        if !wait_for_leftover(timeout) {
+            table.row(&[runtime.to_string(), "left over".to_owned()]);
+            continue;
+        }
+        let key = if runtime == Runtime::Native {
+            match &sample.native {
+                Some(native) if sample.args.len() == 1 => native.clone(),
+                _ => {
+                    table.row(&[runtime.to_string(), "NA".to_owned()]);
+                    continue;
+                }
+            }
+        } else {
+            let wasm = sample.wasm.clone();
+            match run_with_timeout(timeout, move || {
+                dispatch::compile_artifact(runtime, &wasm).unwrap()
+            }) {
+                Outcome::Finished(key, _) => key,
+                outcome => {
+                    let cell = cell(outcome, runtime, Phase::AotCompile, |_| String::new());
+                    table.row(&[runtime.to_string(), format!("compile {}", cell)]);
+                    continue;
+                }
+            }
+        };
+
+        let mut starts = vec![];
+        for _ in 0..opts.runs {
+            if drop_caches {
+                if let Err(e) = cold::drop_caches() {
+                    eprintln!("not dropping the page cache: {}", e);
+                    drop_caches = false;
+                }
+            }
+            let mut command = process::Command::new(&exe);
+            command
+                .arg("cold-start-child")
+                .arg(runtime.name())
+                .arg(&key)
+                .arg(&sample.export)
+                .args(sample.args.iter().map(u32::to_string));
+            match cold::spawn(command, timeout) {
+                Ok(start) => starts.push(start),
+                Err(e) => {
+                    eprintln!("{}: {}", runtime, e);
+                    break;
+                }
+            }
+        }
+        if starts.len() < opts.runs as usize {
+            table.row(&[runtime.to_string(), "failed".to_owned()]);
+            continue;
+        }
+
+        let ns = |d: Duration| format_time(d.as_nanos() as f64);
+        let totals: Vec<Duration> = starts.iter().map(|s| s.total).collect();
+        let in_process: Duration = starts.iter().map(|s| s.in_process).sum();
+        table.row(&[
+            runtime.to_string(),
+            ns(totals.iter().sum::<Duration>() / opts.runs),
+            ns(*totals.iter().min().unwrap()),
+            ns(*totals.iter().max().unwrap()),
+            ns(in_process / opts.runs),
+            starts[0].result.to_string(),
+        ]);
+    }
+
+    print!("{}", table.render());
+    Ok(())
+}
+
+fn density(opts: DensityOpts) -> Result<(), String> {
+    let mut sample = resolve(&opts.module)?;
+    if let Some(export) = &opts.export {
+        sample.export = export.clone();
+    }
+    if !opts.args.is_empty() {
+        sample.args = opts.args.clone();
+    }
+    std::fs::create_dir_all("./tmp/lucet").map_err(|e| e.to_string())?;
+
+    let runtimes = if opts.runtimes.is_empty() {
+        Runtime::ALL
+            .iter()
+            .copied()
+            .filter(|r| *r != Runtime::Native)
+            .collect()
+    } else {
+        opts.runtimes.clone()
+    };
+    let limits = Limits {
+        rss: opts.max_rss * 1024 * 1024,
+        instances: opts.max_instances,
+    };
+
+    println!(
+        "{} ({}), instances alive at once{}\n",
+        opts.module,
+        format_size(sample.wasm.len() as u64),
+        if opts.call {
+            format!(
+                ", calling `{}` with {:?} on each",
+                sample.export, sample.args
+            )
+        } else {
+            String::new()
+        }
+    );
+    let mut table = Table::new(&[
+        "",
+        "instances",
+        "stopped by",
+        "rss",
+        "rss / instance",
+        "virtual",
+        "virtual / instance",
+    ]);
+
+    let sample = Arc::new(sample);
+    for runtime in runtimes {
+        if runtime == Runtime::Native {
+            table.row(&[runtime.to_string(), "NA".to_owned()]);
+            continue;
+        }
+        let (sample, call) = (sample.clone(), opts.call);
+        let outcome = run_with_timeout(Duration::from_secs_f64(opts.timeout), move || {
+            density::instances(
+                runtime,
+                &sample.wasm,
+                &sample.export,
+                &sample.args,
+                call,
+                limits,
+            )
+        });
+        let mut cells = vec![runtime.to_string()];
+        match outcome {
+            Outcome::Finished(density, _) => cells.extend(density.cells()),
+            // Its instances stay alive, the numbers of the next runtimes would
+            // be off
+            Outcome::TimedOut(after) => {
+                table.row(&[runtime.to_string(), format!("timed out after {:?}", after)]);
+                break;
+            }
+            Outcome::Failed(message) => {
+                eprintln!("{}: {}", runtime, message);
+                cells.push("failed".to_owned());
+            }
+        }
+        table.row(&cells);
+    }
+
+    print!("{}", table.render());
+    Ok(())
+}
+
+fn traps(opts: TrapsOpts) -> Result<(), String> {
+    let samples: Vec<Sample> = samples::load_traps()?
+        .into_iter()
+        .filter(|s| opts.samples.is_empty() || opts.samples.contains(&s.name))
+        .collect();
+    if let Some(name) = opts
+        .samples
+        .iter()
+        .find(|name| samples.iter().all(|s| s.name != **name))
+    {
+        return Err(format!("no trap sample {}", name));
+    }
+    std::fs::create_dir_all("./tmp/lucet").map_err(|e| e.to_string())?;
+    // Spawned processes inherit the affinity and nice value
+    isolation::apply();
+
+    let runtimes = if opts.runtimes.is_empty() {
+        Runtime::ALL
+            .iter()
+            .copied()
+            .filter(|r| *r != Runtime::Native)
+            .collect()
+    } else {
+        opts.runtimes.clone()
+    };
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let timeout = Duration::from_secs_f64(opts.timeout);
+
+    println!(
+        "{} trapping calls per runtime, each on a fresh instance\n",
+        opts.iterations
+    );
+    let mut table = Table::new(&["", "runtime", "trap", "latency", "instance after"]);
+
+    for sample in &samples {
+        for &runtime in &runtimes {
+            let mut command = process::Command::new(&exe);
+            command
+                .arg("trap-child")
+                .arg(runtime.name())
+                .arg(&sample.name)
+                .arg(opts.iterations.to_string());
+            let handled = cold::output(command, timeout).and_then(|(stdout, _)| {
+                Handled::parse(&stdout)
+                    .ok_or_else(|| format!("unexpected output `{}`", stdout.trim()))
+            });
+
+            let mut cells = vec![sample.name.clone(), runtime.to_string()];
+            match handled {
+                Ok(handled) => cells.extend(vec![
+                    match sample.trap {
+                        Some(expected) if !handled.kind.is(expected) => {
+                            format!("{} (expected {})", handled.kind, expected)
+                        }
+                        _ => handled.kind.to_string(),
+                    },
+                    format_time(handled.latency.as_nanos() as f64),
+                    if handled.usable { "usable" } else { "broken" }.to_owned(),
+                ]),
+                Err(e) => {
+                    eprintln!("{} on {}: {}", sample.name, runtime, e);
+                    // A trap the runtime doesn't catch takes the process down
+                    cells.push(
+                        if e.starts_with("signal") {
+                            "crashed"
+                        } else if e.starts_with("timed out") {
+                            "timed out"
+                        } else {
+                            "failed"
+                        }
+                        .to_owned(),
+                    );
+                }
+            }
+            table.row(&cells);
+        }
+    }
+
+    print!("{}", table.render());
+    Ok(())
+}
+
+fn export(baseline: &str, criterion_dir: Option<PathBuf>) -> Result<(), String> {
+    let criterion_dir = criterion_dir.unwrap_or_else(|| target_dir().join("criterion"));
+    let dir = output_dir();
+    let results = Results::collect(&criterion_dir, baseline, &dir)?;
+    if results.is_empty() {
+        return Err(format!(
+            "no results in {} or {}, run `cargo bench` first",
+            criterion_dir.display(),
+            dir.display()
+        ));
+    }
+    results.write(&dir)?;
+    println!(
+        "{} results written to {}",
+        results.len(),
+        dir.join("results.{json,csv}").display()
+    );
+    Ok(())
+}
+
+fn report(readme: Option<PathBuf>, html: bool) -> Result<(), String> {
+    let results = current_results()?;
+    let samples = samples::load_default()?;
+    let report = Report::new(&results, &samples);
+    if html {
+        let path = output_dir().join("report.html");
+        fs::write(&path, report.html()).map_err(|e| e.to_string())?;
+        eprintln!("charts written to {}", path.display());
+    }
+    match readme {
+        Some(path) => {
+            let markdown = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            fs::write(&path, report.update(&markdown)?).map_err(|e| e.to_string())
+        }
+        None => {
+            print!("{}", report.render());
+            Ok(())
+        }
+    }
+}
+
+fn current_results() -> Result<Results, String> {
+    Results::load(&output_dir().join("results.json"))
+        .map_err(|e| format!("{}, run `wasm-bench export` first", e))
+}
+
+fn save(name: Option<String>) -> Result<(), String> {
+    let results = current_results()?;
+    let name = name
+        .or_else(history::commit)
+        .ok_or("no name given and not in a git repository")?;
+    let path = Run::new(Meta::new(&name), &results).save(&history_dir())?;
+    println!("{} results saved to {}", results.len(), path.display());
+    Ok(())
+}
+
+fn list_history() -> Result<(), String> {
+    let mut table = Table::new(&["", "commit", "results", "versions"]);
+    for run in Run::list(&history_dir())? {
+        let versions: Vec<String> = run
+            .meta
+            .versions
+            .iter()
+            .filter(|(name, _)| ["wasmer-runtime", "lucet-runtime"].contains(&name.as_str()))
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect();
+        table.row(&[
+            run.meta.name.clone(),
+            run.meta.commit.clone().unwrap_or_default(),
+            run.results.len().to_string(),
+            versions.join(", "),
+        ]);
+    }
+    print!("{}", table.render());
+    Ok(())
+}
+
+fn compare(opts: CompareOpts) -> Result<(), String> {
+    let dir = history_dir();
+    let baseline = Run::load(&dir, &opts.baseline)?;
+    let (current, name, digests) = match &opts.against {
+        Some(name) => {
+            let run = Run::load(&dir, name)?;
+            (run.results(), name.clone(), run.meta.samples)
+        }
+        None => (current_results()?, "current".to_owned(), sample_digests()),
+    };
+    if baseline.meta.versions != built_versions() && opts.against.is_none() {
+        println!("runtime versions differ from {}\n", baseline.meta.name);
+    }
+    if baseline.meta.samples.is_empty() {
+        println!(
+            "{} has no sample digests, its samples may differ\n",
+            baseline.meta.name
+        );
+    } else {
+        let changed = changed_samples(&baseline.meta.samples, &digests);
+        if !changed.is_empty() {
+            println!(
+                "samples rebuilt since {}: {}\n",
+                baseline.meta.name,
+                changed.join(", ")
+            );
+        }
+    }
+    if let (Some(before), Some(after)) = (&baseline.host, &current.host) {
+        if before != after {
+            println!(
+                "measured on a different host than {}\n  {}\n  {}\n",
+                baseline.meta.name, before, after
+            );
+        }
+    }
+
+    let comparisons = history::compare(&baseline.results(), &current, opts.threshold / 100.0)?;
+    let mut table = Table::new(&["", baseline.meta.name.as_str(), name.as_str(), "change", ""]);
+    let mut regressions = 0;
+    for c in &comparisons {
+        let verdict = match c.verdict {
+            Verdict::Regressed => "regressed",
+            Verdict::Improved => "improved",
+            Verdict::Unchanged => continue,
+        };
+        if c.verdict == Verdict::Regressed {
+            regressions += 1;
+        }
+        table.row(&[
+            c.id.clone(),
+            format_mean(&c.measurement, c.baseline),
+            format_mean(&c.measurement, c.current),
+            format!("{:+.1}%", c.change * 100.0),
+            verdict.to_owned(),
+        ]);
+    }
+    if !table.is_empty() {
+        println!("{}", table.render());
+    }
+    println!(
+        "{} results compared, {} regressed, {} improved",
+        comparisons.len(),
+        regressions,
+        comparisons
+            .iter()
+            .filter(|c| c.verdict == Verdict::Improved)
+            .count()
+    );
+
+    if regressions > 0 {
+        return Err(format!(
+            "{} regressions beyond {}% against {}",
+            regressions, opts.threshold, baseline.meta.name
+        ));
+    }
+    Ok(())
+}
+
+// Times from here on, the process start up before it is only in the parent's total
+fn cold_start_child(runtime: Runtime, key: &str, export: &str, args: &[u32]) -> Result<(), String> {
+    let start = Instant::now();
+    let result = if runtime == Runtime::Native {
+        let run = dispatch::native(key).ok_or_else(|| format!("no native {}", key))?;
+        run(args[0])
+    } else {
+        dispatch::run_artifact(runtime, key, export, args)
+    };
+    println!("{}", cold::report(result, start.elapsed()));
+    Ok(())
+}
+
+// Prints how the trap was handled, if the process survives it
+fn trap_child(runtime: Runtime, name: &str, iterations: u32) -> Result<(), String> {
+    let sample = samples::load_traps()?
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| format!("no trap sample {}", name))?;
+    let handled = trap::handle(
+        runtime,
+        &sample.wasm,
+        &sample.export,
+        &sample.args,
+        iterations,
+    )?;
+    println!("{}", handled.report());
+    Ok(())
+}
+
+fn main() {
+    let result = match Command::from_args() {
+        Command::Run(opts) => run(opts),
+        Command::Samples => list_samples(),
+        Command::ColdStart(opts) => cold_start(opts),
+        Command::Density(opts) => density(opts),
+        Command::Traps(opts) => traps(opts),
+        Command::Export {
+            baseline,
+            criterion_dir,
+        } => export(&baseline, criterion_dir),
+        Command::Report { readme, html } => report(readme, html),
+        Command::Save { name } => save(name),
+        Command::History => list_history(),
+        Command::Compare(opts) => compare(opts),
+        Command::ColdStartChild {
+            runtime,
+            key,
+            export,
+            args,
+        } => cold_start_child(runtime, &key, &export, &args),
+        Command::TrapChild {
+            runtime,
+            sample,
+            iterations,
+        } => trap_child(runtime, &sample, iterations),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
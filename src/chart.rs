@@ -0,0 +1,462 @@
+use crate::table::{format_count, format_size, format_time};
+use std::fmt::Write;
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 320.0;
+const LEFT: f64 = 70.0;
+const RIGHT: f64 = 160.0;
+const TOP: f64 = 20.0;
+const BOTTOM: f64 = 40.0;
+
+// Colors of the series, in order
+pub const COLORS: &[&str] = &[
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1",
+];
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// A log10 scale over whole decades, as times of different runtimes are orders
+// of magnitude apart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    lo: i32,
+    hi: i32,
+}
+
+impl Scale {
+    pub fn new<I: IntoIterator<Item = f64>>(values: I) -> Option<Self> {
+        let logs: Vec<f64> = values
+            .into_iter()
+            .filter(|v| *v > 0.0)
+            .map(f64::log10)
+            .collect();
+        let lo = logs.iter().cloned().fold(f64::INFINITY, f64::min);
+        let hi = logs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if logs.is_empty() {
+            return None;
+        }
+        let (lo, hi) = (lo.floor() as i32, hi.ceil() as i32);
+        Some(Self {
+            lo,
+            hi: hi.max(lo + 1),
+        })
+    }
+
+    // Where `value` is between the lowest (0) and highest (1) decade
+    pub fn ratio(&self, value: f64) -> f64 {
+        (value.log10() - f64::from(self.lo)) / f64::from(self.hi - self.lo)
+    }
+
+    pub fn decades(&self) -> impl Iterator<Item = f64> {
+        (self.lo..=self.hi).map(|e| 10f64.powi(e))
+    }
+}
+
+// What the values on an axis are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Nanos,
+    Bytes,
+    // Events per iteration, eg. page faults
+    Count,
+}
+
+impl Unit {
+    // A value as in the tables
+    pub fn format(self, value: f64) -> String {
+        match self {
+            Unit::Nanos => format_time(value),
+            Unit::Bytes => format_size(value as u64),
+            Unit::Count => format_count(value),
+        }
+    }
+}
+
+// An axis' title and unit
+#[derive(Debug, Clone, Copy)]
+pub struct Axis<'a> {
+    pub title: &'a str,
+    pub unit: Unit,
+}
+
+// A decade of the unit, eg. `100 us`, `10 kB` or `1 k`
+pub fn decade(value: f64, unit: Unit) -> String {
+    let units: &[(f64, &str)] = match unit {
+        Unit::Nanos => &[
+            (1e9, "s"),
+            (1e6, "ms"),
+            (1e3, "us"),
+            (1.0, "ns"),
+            (1e-3, "ps"),
+        ],
+        Unit::Bytes => &[(1e9, "GB"), (1e6, "MB"), (1e3, "kB"), (1.0, "B")],
+        Unit::Count if value < 1.0 => return value.to_string(),
+        Unit::Count => &[(1e9, "G"), (1e6, "M"), (1e3, "k"), (1.0, "")],
+    };
+    let (factor, name) = units
+        .iter()
+        .find(|(factor, _)| value >= *factor * 0.999)
+        .unwrap_or(&units[units.len() - 1]);
+    format!("{} {}", (value / factor).round(), name)
+        .trim_end()
+        .to_owned()
+}
+
+pub struct Series {
+    pub name: String,
+    pub values: Vec<Option<f64>>,
+}
+
+fn svg(out: &mut String, body: &str) {
+    write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"11\">\n{}</svg>\n",
+        body,
+        w = WIDTH,
+        h = HEIGHT
+    )
+    .unwrap();
+}
+
+// Horizontal grid lines and labels of a log scale on the y axis
+fn y_grid(out: &mut String, scale: &Scale, unit: Unit) {
+    for value in scale.decades() {
+        let y = TOP + (HEIGHT - TOP - BOTTOM) * (1.0 - scale.ratio(value));
+        writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{y:.1}\" x2=\"{}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>\
+             <text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            LEFT,
+            WIDTH - RIGHT,
+            LEFT - 6.0,
+            y + 4.0,
+            decade(value, unit),
+            y = y
+        )
+        .unwrap();
+    }
+}
+
+fn legend(out: &mut String, names: &[&str]) {
+    for (i, name) in names.iter().enumerate() {
+        let y = TOP + 16.0 * i as f64;
+        writeln!(
+            out,
+            "<rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\">{}</text>",
+            WIDTH - RIGHT + 12.0,
+            y,
+            COLORS[i % COLORS.len()],
+            WIDTH - RIGHT + 26.0,
+            y + 9.0,
+            escape(name)
+        )
+        .unwrap();
+    }
+}
+
+// Bars of each series grouped by `groups` on a log scale, None when there is
+// nothing to draw
+pub fn bars(groups: &[String], series: &[Series], unit: Unit) -> Option<String> {
+    let scale = Scale::new(
+        series
+            .iter()
+            .flat_map(|s| s.values.iter().flatten().cloned()),
+    )?;
+    let plot = HEIGHT - TOP - BOTTOM;
+    let group_width = (WIDTH - LEFT - RIGHT) / groups.len() as f64;
+    let bar_width = group_width * 0.8 / series.len() as f64;
+
+    let mut body = String::new();
+    y_grid(&mut body, &scale, unit);
+    for (g, group) in groups.iter().enumerate() {
+        let x0 = LEFT + group_width * g as f64 + group_width * 0.1;
+        for (i, s) in series.iter().enumerate() {
+            let value = match s.values.get(g).cloned().flatten() {
+                Some(value) => value,
+                None => continue,
+            };
+            let height = plot * scale.ratio(value).max(0.0);
+            writeln!(
+                body,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+                 <title>{}, {}: {}</title></rect>",
+                x0 + bar_width * i as f64,
+                TOP + plot - height,
+                bar_width,
+                height,
+                COLORS[i % COLORS.len()],
+                escape(group),
+                escape(&s.name),
+                unit.format(value)
+            )
+            .unwrap();
+        }
+        writeln!(
+            body,
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x0 + group_width * 0.4,
+            HEIGHT - BOTTOM + 16.0,
+            escape(group)
+        )
+        .unwrap();
+    }
+    let names: Vec<&str> = series.iter().map(|s| s.name.as_str()).collect();
+    legend(&mut body, &names);
+
+    let mut out = String::new();
+    svg(&mut out, &body);
+    Some(out)
+}
+
+pub struct Point {
+    pub label: String,
+    pub series: usize,
+    pub x: f64,
+    pub y: f64,
+}
+
+// Points on log scales in both directions, colored by series and labelled
+pub fn scatter(series: &[&str], points: &[Point], x: Axis, y: Axis) -> Option<String> {
+    plot(series, points, x, y, false)
+}
+
+// Points of each series joined by a line from left to right, eg. a cost
+// against module size, with the labels only in their tooltips
+pub fn lines(series: &[&str], points: &[Point], x: Axis, y: Axis) -> Option<String> {
+    plot(series, points, x, y, true)
+}
+
+fn plot(
+    series: &[&str],
+    points: &[Point],
+    x_axis: Axis,
+    y_axis: Axis,
+    lines: bool,
+) -> Option<String> {
+    let x_scale = Scale::new(points.iter().map(|p| p.x))?;
+    let y_scale = Scale::new(points.iter().map(|p| p.y))?;
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let plot_height = HEIGHT - TOP - BOTTOM;
+
+    let mut body = String::new();
+    y_grid(&mut body, &y_scale, y_axis.unit);
+    for value in x_scale.decades() {
+        let x = LEFT + plot_width * x_scale.ratio(value);
+        writeln!(
+            body,
+            "<line x1=\"{x:.1}\" y1=\"{}\" x2=\"{x:.1}\" y2=\"{}\" stroke=\"#ddd\"/>\
+             <text x=\"{x:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            TOP,
+            TOP + plot_height,
+            TOP + plot_height + 14.0,
+            decade(value, x_axis.unit),
+            x = x
+        )
+        .unwrap();
+    }
+    let position = |p: &Point| {
+        (
+            LEFT + plot_width * x_scale.ratio(p.x),
+            TOP + plot_height * (1.0 - y_scale.ratio(p.y)),
+        )
+    };
+    let points: Vec<&Point> = points.iter().filter(|p| p.x > 0.0 && p.y > 0.0).collect();
+    if lines {
+        for i in 0..series.len() {
+            let mut line: Vec<&Point> = points.iter().copied().filter(|p| p.series == i).collect();
+            line.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+            let coords: Vec<String> = line
+                .iter()
+                .map(|p| {
+                    let (x, y) = position(p);
+                    format!("{:.1},{:.1}", x, y)
+                })
+                .collect();
+            writeln!(
+                body,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\"/>",
+                coords.join(" "),
+                COLORS[i % COLORS.len()]
+            )
+            .unwrap();
+        }
+    }
+    for point in points {
+        let (x, y) = position(point);
+        let color = COLORS[point.series % COLORS.len()];
+        if lines {
+            writeln!(
+                body,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"><title>{}</title></circle>",
+                x,
+                y,
+                color,
+                escape(&point.label)
+            )
+            .unwrap();
+            continue;
+        }
+        writeln!(
+            body,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\"><title>{}</title></circle>\
+             <text x=\"{:.1}\" y=\"{:.1}\" fill=\"#555\">{}</text>",
+            x,
+            y,
+            color,
+            escape(&point.label),
+            x + 6.0,
+            y - 4.0,
+            escape(&point.label)
+        )
+        .unwrap();
+    }
+    writeln!(
+        body,
+        "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>\
+         <text x=\"12\" y=\"{:.1}\" text-anchor=\"middle\" transform=\"rotate(-90 12 {:.1})\">{}</text>",
+        LEFT + plot_width / 2.0,
+        HEIGHT - 6.0,
+        escape(x_axis.title),
+        TOP + plot_height / 2.0,
+        TOP + plot_height / 2.0,
+        escape(y_axis.title)
+    )
+    .unwrap();
+    legend(&mut body, series);
+
+    let mut out = String::new();
+    svg(&mut out, &body);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale() {
+        let scale = Scale::new(vec![6_000_000.0, 9.1e9, 0.0]).unwrap();
+        assert_eq!(scale, Scale { lo: 6, hi: 10 });
+        assert_eq!(scale.ratio(1e8), 0.5);
+        assert_eq!(scale.decades().count(), 5);
+        assert_eq!(Scale::new(vec![100.0]).unwrap(), Scale { lo: 2, hi: 3 });
+        assert_eq!(Scale::new(vec![]), None);
+    }
+
+    #[test]
+    fn test_decade() {
+        assert_eq!(decade(1.0, Unit::Nanos), "1 ns");
+        assert_eq!(decade(100_000.0, Unit::Nanos), "100 us");
+        assert_eq!(decade(1e10, Unit::Nanos), "10 s");
+        assert_eq!(decade(0.1, Unit::Nanos), "100 ps");
+        assert_eq!(decade(10_000.0, Unit::Bytes), "10 kB");
+        assert_eq!(decade(1.0, Unit::Bytes), "1 B");
+        assert_eq!(decade(1000.0, Unit::Count), "1 k");
+        assert_eq!(decade(10.0, Unit::Count), "10");
+        assert_eq!(decade(0.1, Unit::Count), "0.1");
+    }
+
+    #[test]
+    fn test_bars() {
+        let groups = vec!["add-one".to_owned(), "<fib>".to_owned()];
+        let series = vec![
+            Series {
+                name: "wasmer/llvm".to_owned(),
+                values: vec![Some(1.1674e9), None],
+            },
+            Series {
+                name: "lucet".to_owned(),
+                values: vec![Some(19_614_000.0), Some(101_630_000.0)],
+            },
+        ];
+        let svg = bars(&groups, &series, Unit::Nanos).unwrap();
+        assert!(svg.starts_with("<svg "));
+        assert_eq!(svg.matches("<rect").count(), 3 + 2);
+        assert!(svg.contains("&lt;fib&gt;"));
+        assert!(svg.contains(">10 ms</text>"));
+
+        let empty = vec![Series {
+            name: "lucet".to_owned(),
+            values: vec![None],
+        }];
+        assert_eq!(bars(&groups[..1], &empty, Unit::Nanos), None);
+    }
+
+    #[test]
+    fn test_scatter() {
+        let points = vec![
+            Point {
+                label: "fibonacci".to_owned(),
+                series: 0,
+                x: 6_774_600.0,
+                y: 6_860.1,
+            },
+            Point {
+                label: "fibonacci".to_owned(),
+                series: 1,
+                x: 9_109_700_000.0,
+                y: 1_954.9,
+            },
+        ];
+        let (x, y) = (
+            Axis {
+                title: "compile",
+                unit: Unit::Nanos,
+            },
+            Axis {
+                title: "execute",
+                unit: Unit::Nanos,
+            },
+        );
+        let svg = scatter(&["singlepass", "llvm"], &points, x, y).unwrap();
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert!(svg.contains(">10 s</text>"));
+        assert!(!svg.contains("<polyline"));
+    }
+
+    #[test]
+    fn test_lines() {
+        let point = |series, x, y| Point {
+            label: format!("{} bytes", x),
+            series,
+            x,
+            y,
+        };
+        let points = vec![
+            point(0, 10_000.0, 4e6),
+            point(0, 100.0, 1e6),
+            point(0, 1000.0, 2e6),
+            point(1, 100.0, 9e7),
+        ];
+        let x = Axis {
+            title: "module size",
+            unit: Unit::Bytes,
+        };
+        let y = Axis {
+            title: "compile",
+            unit: Unit::Nanos,
+        };
+        let svg = lines(&["singlepass", "llvm"], &points, x, y).unwrap();
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert_eq!(svg.matches("<circle").count(), 4);
+        assert!(svg.contains(">10 kB</text>"));
+        assert!(svg.contains(">100 ms</text>"));
+        // From left to right, whatever the order of the points
+        let first = svg.split("points=\"").nth(1).unwrap();
+        let xs: Vec<f64> = first
+            .split('"')
+            .next()
+            .unwrap()
+            .split(' ')
+            .map(|p| p.split(',').next().unwrap().parse().unwrap())
+            .collect();
+        assert!(xs.windows(2).all(|w| w[0] < w[1]), "{:?}", xs);
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::io;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// One cold start: the time from spawning the process until it exited, and the
+// part the child spent between starting `main` and returning from the call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Start {
+    pub total: Duration,
+    pub in_process: Duration,
+    pub result: u32,
+}
+
+// What the child prints on stdout when it is done, `result in_process_ns`
+pub fn report(result: u32, in_process: Duration) -> String {
+    format!("{} {}", result, in_process.as_nanos())
+}
+
+fn parse(stdout: &str) -> Option<(u32, Duration)> {
+    let mut parts = stdout.trim().rsplit('\n').next()?.split(' ');
+    let result = parts.next()?.parse().ok()?;
+    let ns: u64 = parts.next()?.parse().ok()?;
+    Some((result, Duration::from_nanos(ns)))
+}
+
+// Spawns `command`, which should end by printing `report`, and waits for it,
+// killing it after `timeout`
+pub fn spawn(command: Command, timeout: Duration) -> Result<Start, String> {
+    let (stdout, total) = output(command, timeout)?;
+    let (result, in_process) =
+        parse(&stdout).ok_or_else(|| format!("unexpected output `{}`", stdout.trim()))?;
+    Ok(Start {
+        total,
+        in_process,
+        result,
+    })
+}
+
+// Runs `command` to the end, killing it after `timeout`, and returns its stdout
+// and how long it took. Fails with the exit status, or the signal it was killed
+// by, and the last line of its stderr when it didn't succeed.
+pub fn output(mut command: Command, timeout: Duration) -> Result<(String, Duration), String> {
+    let start = Instant::now();
+    let child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    let pid = child.id() as libc::pid_t;
+    let (done, rx) = mpsc::channel::<()>();
+    let watchdog = thread::spawn(move || match rx.recv_timeout(timeout) {
+        Err(RecvTimeoutError::Timeout) => {
+            unsafe { libc::kill(pid, libc::SIGKILL) };
+            true
+        }
+        _ => false,
+    });
+
+    let output = child.wait_with_output().map_err(|e| e.to_string());
+    let total = start.elapsed();
+    let _ = done.send(());
+    if watchdog.join().unwrap_or(false) {
+        return Err(format!("timed out after {:?}", timeout));
+    }
+
+    let output = output?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "{}: {}",
+            output.status,
+            stderr.lines().last().unwrap_or("")
+        ));
+    }
+    Ok((String::from_utf8_lossy(&output.stdout).into_owned(), total))
+}
+
+// Drops the page cache, so the next start reads the runtime and the artifact
+// from disk. Needs root.
+pub fn drop_caches() -> io::Result<()> {
+    unsafe { libc::sync() };
+    fs::write("/proc/sys/vm/drop_caches", "3")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[test]
+    fn test_parse() {
+        let out = report(89, Duration::from_micros(1500));
+        assert_eq!(out, "89 1500000");
+        assert_eq!(
+            parse(&format!("loading\n{}\n", out)),
+            Some((89, Duration::from_micros(1500)))
+        );
+        assert_eq!(parse("89"), None);
+    }
+
+    #[test]
+    fn test_spawn() {
+        let start = spawn(sh("echo 89 1000"), Duration::from_secs(10)).unwrap();
+        assert_eq!(start.result, 89);
+        assert_eq!(start.in_process, Duration::from_nanos(1000));
+        assert!(start.total > start.in_process);
+
+        let error = spawn(sh("echo trap >&2; exit 3"), Duration::from_secs(10)).unwrap_err();
+        assert!(error.ends_with("trap"), "{}", error);
+
+        let error = spawn(sh("exec sleep 5"), Duration::from_millis(100)).unwrap_err();
+        assert!(error.starts_with("timed out"), "{}", error);
+
+        let error = spawn(sh("kill -SEGV $$"), Duration::from_secs(10)).unwrap_err();
+        assert!(error.starts_with("signal"), "{}", error);
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+fn clock(id: libc::clockid_t) -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let ret = unsafe { libc::clock_gettime(id, &mut ts) };
+    assert_eq!(ret, 0, "clock_gettime failed");
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+// CPU time the calling thread has used, user and system
+pub fn thread_time() -> Duration {
+    clock(libc::CLOCK_THREAD_CPUTIME_ID)
+}
+
+// CPU time all threads of the process have used, including the background
+// threads of a runtime
+pub fn process_time() -> Duration {
+    clock(libc::CLOCK_PROCESS_CPUTIME_ID)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn spin(d: Duration) {
+        let start = thread_time();
+        while thread_time() - start < d {}
+    }
+
+    #[test]
+    fn test_thread_time() {
+        let start = thread_time();
+        thread::sleep(Duration::from_millis(50));
+        assert!(thread_time() - start < Duration::from_millis(25));
+
+        spin(Duration::from_millis(10));
+        assert!(thread_time() - start >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_process_time() {
+        let start = process_time();
+        let thread_start = thread_time();
+        thread::spawn(|| spin(Duration::from_millis(20)))
+            .join()
+            .unwrap();
+        assert!(process_time() - start >= Duration::from_millis(20));
+        assert!(thread_time() - thread_start < Duration::from_millis(20));
+    }
+}
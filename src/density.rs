@@ -0,0 +1,220 @@
+use crate::lucet_runner;
+use crate::matrix::Runtime;
+use crate::memory;
+use crate::table::format_size;
+use crate::wasmer_runner::Wrapper;
+use std::fmt;
+
+// When to stop making instances
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    // Growth of the RSS in bytes
+    pub rss: u64,
+    pub instances: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    Rss,
+    Instances,
+    Failed(String),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Rss => f.write_str("rss budget"),
+            Stop::Instances => f.write_str("instance limit"),
+            Stop::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+// How many instances were alive at once, and what they took on top of what the
+// process used before. Sizes are None without /proc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Density {
+    pub instances: usize,
+    pub rss: Option<u64>,
+    pub virtual_size: Option<u64>,
+    pub stop: Stop,
+}
+
+impl Density {
+    pub fn rss_per_instance(&self) -> Option<u64> {
+        Some(self.rss? / self.instances.max(1) as u64)
+    }
+
+    pub fn virtual_per_instance(&self) -> Option<u64> {
+        Some(self.virtual_size? / self.instances.max(1) as u64)
+    }
+
+    // `instances`, `stopped by`, `rss`, `rss per instance`, `virtual` and
+    // `virtual per instance`
+    pub fn cells(&self) -> Vec<String> {
+        let size = |v: Option<u64>| v.map(format_size).unwrap_or_else(|| "NA".to_owned());
+        vec![
+            self.instances.to_string(),
+            self.stop.to_string(),
+            size(self.rss),
+            size(self.rss_per_instance()),
+            size(self.virtual_size),
+            size(self.virtual_per_instance()),
+        ]
+    }
+}
+
+// Keeps every instance `instantiate` makes alive until the RSS grew by the
+// budget, there are as many as the limit or it fails, and drops them after
+// measuring
+pub fn fill<I, F>(limits: Limits, mut instantiate: F) -> Density
+where
+    F: FnMut() -> Result<I, String>,
+{
+    let (rss, virtual_size) = (memory::rss(), memory::virtual_size());
+    let grown = |before: Option<u64>, now: Option<u64>| Some(now?.saturating_sub(before?));
+
+    let mut instances = vec![];
+    let stop = loop {
+        if instances.len() >= limits.instances {
+            break Stop::Instances;
+        }
+        match instantiate() {
+            Ok(instance) => instances.push(instance),
+            Err(e) => break Stop::Failed(e),
+        }
+        if matches!(grown(rss, memory::rss()), Some(grown) if grown >= limits.rss) {
+            break Stop::Rss;
+        }
+    };
+
+    Density {
+        instances: instances.len(),
+        rss: grown(rss, memory::rss()),
+        virtual_size: grown(virtual_size, memory::virtual_size()),
+        stop,
+    }
+}
+
+// Instances of `wasm` alive at once, compiled once, with `export` called once on
+// each with `call` so the memory it touches counts. Lucet gets a region per
+// instance, as in the instantiate phase.
+pub fn instances(
+    runtime: Runtime,
+    wasm: &[u8],
+    export: &str,
+    args: &[u32],
+    call: bool,
+    limits: Limits,
+) -> Density {
+    match runtime {
+        Runtime::Native => panic!("{} has no instances", runtime),
+        Runtime::Lucet => {
+            let moduleid = lucet_runner::compile(wasm);
+            fill(limits, || {
+                let mut instance =
+                    lucet_runner::try_instantiate(&moduleid).map_err(|e| e.to_string())?;
+                if call {
+                    lucet_runner::try_call(&mut instance, export, args)
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(instance)
+            })
+        }
+        _ => {
+            let wrapper = Wrapper::new(runtime.backend().unwrap());
+            let module = wrapper.compile(wasm);
+            fill(limits, || {
+                let instance = wrapper.instantiate(&module).map_err(|e| e.to_string())?;
+                if call {
+                    wrapper
+                        .try_call(&instance, export, args)
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(instance)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::black_box;
+
+    const MB: usize = 1024 * 1024;
+    static WASM: &[u8] = include_bytes!("../wasm-sample/fibonacci.wasm");
+
+    #[test]
+    fn test_fill() {
+        let limits = Limits {
+            rss: u64::MAX,
+            instances: 5,
+        };
+        let density = fill(limits, || Ok(()));
+        assert_eq!(density.instances, 5);
+        assert_eq!(density.stop, Stop::Instances);
+
+        let mut n = 0;
+        let density = fill(limits, || {
+            n += 1;
+            if n < 3 {
+                Ok(())
+            } else {
+                Err("full".to_owned())
+            }
+        });
+        assert_eq!(density.instances, 2);
+        assert_eq!(density.cells()[1], "failed: full");
+    }
+
+    #[test]
+    fn test_rss() {
+        let limits = Limits {
+            rss: 8 * MB as u64,
+            instances: 64,
+        };
+        // Touched, so they count towards the RSS
+        let density = fill(limits, || Ok(black_box(vec![1u8; MB])));
+        assert_eq!(density.stop, Stop::Rss);
+        assert!(density.instances < 64, "{:?}", density);
+        assert!(density.virtual_per_instance().is_some());
+    }
+
+    #[test]
+    fn test_instances() {
+        let limits = Limits {
+            rss: u64::MAX,
+            instances: 3,
+        };
+        for &runtime in &[Runtime::WasmerSinglepass, Runtime::Lucet] {
+            let density = instances(runtime, WASM, "run", &[10], true, limits);
+            assert_eq!(density.instances, 3);
+            assert_eq!(density.stop, Stop::Instances);
+        }
+
+        // A trapping call stops filling instead of panicking, and says why
+        let samples = crate::samples::load_traps().unwrap();
+        let unreachable = samples.iter().find(|s| s.name == "unreachable").unwrap();
+        for &runtime in &[Runtime::WasmerSinglepass, Runtime::Lucet] {
+            let density = instances(
+                runtime,
+                &unreachable.wasm,
+                &unreachable.export,
+                &unreachable.args,
+                true,
+                limits,
+            );
+            assert_eq!(density.instances, 0);
+            match density.stop {
+                Stop::Failed(message) => assert!(
+                    message.starts_with("unreachable: "),
+                    "{}: {}",
+                    runtime,
+                    message
+                ),
+                stop => panic!("{} should fail on the trap, got {:?}", runtime, stop),
+            }
+        }
+    }
+}
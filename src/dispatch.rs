@@ -0,0 +1,268 @@
+use crate::lucet_runner;
+use crate::matrix::{Case, Phase, Runtime};
+use crate::wasmer_runner::Wrapper;
+use std::time::{Duration, Instant};
+use wasmer_runtime::Instance;
+
+// Something that repeatedly times a routine, eg. criterion's `Bencher`. Setup
+// happens in `run` before `iter` is called, so only the routine gets timed.
+pub trait Timer {
+    fn iter<O, R: FnMut() -> O>(&mut self, routine: R);
+}
+
+// Times a single call of the routine
+#[derive(Debug, Default)]
+pub struct Once(pub Duration);
+
+impl Timer for Once {
+    fn iter<O, R: FnMut() -> O>(&mut self, mut routine: R) {
+        let start = Instant::now();
+        black_box(routine());
+        self.0 = start.elapsed();
+    }
+}
+
+// Times `iters` calls of the routine after a single warm up call
+#[derive(Debug)]
+pub struct Loop {
+    pub iters: u32,
+    pub times: Vec<Duration>,
+}
+
+impl Loop {
+    pub fn new(iters: u32) -> Self {
+        Self {
+            iters,
+            times: Vec::with_capacity(iters as usize),
+        }
+    }
+
+    pub fn mean(&self) -> Duration {
+        let total: Duration = self.times.iter().sum();
+        total / (self.times.len().max(1) as u32)
+    }
+}
+
+impl Timer for Loop {
+    fn iter<O, R: FnMut() -> O>(&mut self, mut routine: R) {
+        black_box(routine());
+        for _ in 0..self.iters {
+            let start = Instant::now();
+            black_box(routine());
+            self.times.push(start.elapsed());
+        }
+    }
+}
+
+// Same as criterion's black_box without the `real_blackbox` feature
+pub fn black_box<T>(dummy: T) -> T {
+    unsafe {
+        let ret = std::ptr::read_volatile(&dummy);
+        std::mem::forget(dummy);
+        ret
+    }
+}
+
+fn add_one(n: u32) -> u32 {
+    n + 1
+}
+
+fn fibonacci(n: u32) -> u32 {
+    fibonacci::run(n)
+}
+
+fn nbody(n: u32) -> u32 {
+    unsafe { nbody::run(n) }
+}
+
+// Native implementations a sample can name as its baseline in the manifest
+pub fn native(name: &str) -> Option<fn(u32) -> u32> {
+    match name {
+        "add-one" => Some(add_one),
+        "fibonacci" => Some(fibonacci),
+        "nbody" => Some(nbody),
+        _ => None,
+    }
+}
+
+pub fn supported(case: &Case) -> bool {
+    case.phase.supports(case.runtime)
+        && (case.runtime != Runtime::Native
+            || (case.native.as_deref().and_then(native).is_some() && case.args.len() == 1))
+}
+
+// Size in bytes of what the runtime's AOT compile stores, None for native
+pub fn artifact_size(runtime: Runtime, wasm: &[u8]) -> Option<u64> {
+    match runtime {
+        Runtime::Native => None,
+        Runtime::Lucet => Some(lucet_runner::artifact_size(wasm)),
+        _ => {
+            let wrapper = Wrapper::new(runtime.backend()?);
+            Some(wrapper.artifact_size(wasm).unwrap() as u64)
+        }
+    }
+}
+
+// AOT compiles `wasm` and returns the key `run_artifact` loads it with
+pub fn compile_artifact(runtime: Runtime, wasm: &[u8]) -> Option<String> {
+    match runtime {
+        Runtime::Native => None,
+        Runtime::Lucet => Some(lucet_runner::aot_c(wasm)),
+        _ => Some(Wrapper::new(runtime.backend()?).aot_c(wasm).unwrap()),
+    }
+}
+
+// Loads an artifact `compile_artifact` stored, instantiates it and calls `export`
+// once, the way a fresh process starts serving
+pub fn run_artifact(runtime: Runtime, key: &str, export: &str, args: &[u32]) -> u32 {
+    match runtime {
+        Runtime::Native => panic!("{} has no artifact", runtime),
+        Runtime::Lucet => {
+            let mut instance = lucet_runner::instantiate(key);
+            lucet_runner::call(&mut instance, export, args)
+        }
+        _ => {
+            let wrapper = Wrapper::new(runtime.backend().unwrap());
+            let module = wrapper.load(key).unwrap();
+            let instance = wrapper.instantiate(&module).unwrap();
+            wrapper.call(&instance, export, args).unwrap()
+        }
+    }
+}
+
+// Runs the setup of `case` and hands its timed part over to `timer`
+pub fn run<T: Timer>(case: &Case, wasm: &[u8], timer: &mut T) {
+    let export = case.export.as_str();
+    let args = case.args.as_slice();
+
+    if let Some(backend) = case.runtime.backend() {
+        let wrapper = Wrapper::new(backend);
+        let call = |instance: &Instance| wrapper.call(instance, export, black_box(args)).unwrap();
+        match case.phase {
+            Phase::Validate => timer.iter(|| wrapper.validate(wasm)),
+            Phase::Compile => timer.iter(|| wrapper.compile(wasm)),
+            Phase::Instantiate => {
+                let module = wrapper.compile(wasm);
+                timer.iter(|| wrapper.instantiate(&module).unwrap())
+            }
+            Phase::Execute => {
+                let instance = wrapper.prepare(wasm).unwrap();
+                timer.iter(|| call(&instance))
+            }
+            Phase::Jit => timer.iter(|| call(&wrapper.prepare(wasm).unwrap())),
+            Phase::AotCompile => timer.iter(|| wrapper.aot_c(wasm).unwrap()),
+            Phase::AotExecute => {
+                let key = wrapper.aot_c(wasm).unwrap();
+                timer.iter(|| {
+                    let module = wrapper.load(&key).unwrap();
+                    call(&wrapper.instantiate(&module).unwrap())
+                })
+            }
+            Phase::AotTotal => timer.iter(|| {
+                let key = wrapper.aot_c(wasm).unwrap();
+                let module = wrapper.load(&key).unwrap();
+                call(&wrapper.instantiate(&module).unwrap())
+            }),
+        }
+        return;
+    }
+
+    match (case.runtime, case.phase) {
+        (Runtime::Native, Phase::Execute) => {
+            let run = case
+                .native
+                .as_deref()
+                .and_then(native)
+                .expect("no native implementation");
+            let input = args[0];
+            timer.iter(|| run(black_box(input)))
+        }
+        (Runtime::Lucet, Phase::Validate) => timer.iter(|| lucet_runner::validate(wasm)),
+        (Runtime::Lucet, Phase::Compile) => timer.iter(|| lucet_runner::compile(wasm)),
+        (Runtime::Lucet, Phase::Instantiate) => {
+            let moduleid = lucet_runner::compile(wasm);
+            timer.iter(|| lucet_runner::instantiate(&moduleid))
+        }
+        (Runtime::Lucet, Phase::Execute) => {
+            let mut instance = lucet_runner::prepare(wasm);
+            timer.iter(|| lucet_runner::call(&mut instance, export, black_box(args)))
+        }
+        (Runtime::Lucet, Phase::AotCompile) => timer.iter(|| lucet_runner::aot_c(wasm)),
+        (Runtime::Lucet, Phase::AotExecute) => {
+            let moduleid = lucet_runner::aot_c(wasm);
+            timer.iter(|| {
+                let mut instance = lucet_runner::instantiate(&moduleid);
+                lucet_runner::call(&mut instance, export, black_box(args))
+            })
+        }
+        (Runtime::Lucet, Phase::AotTotal) => timer.iter(|| {
+            let moduleid = lucet_runner::aot_c(wasm);
+            let mut instance = lucet_runner::instantiate(&moduleid);
+            lucet_runner::call(&mut instance, export, black_box(args))
+        }),
+        (runtime, phase) => panic!("{} does not support {}", runtime, phase),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static WASM: &[u8] = include_bytes!("../wasm-sample/fibonacci.wasm");
+
+    fn case(phase: Phase, runtime: Runtime) -> Case {
+        Case {
+            phase,
+            sample: "fibonacci".to_owned(),
+            runtime,
+            export: "run".to_owned(),
+            args: vec![10],
+            native: Some("fibonacci".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_native() {
+        assert_eq!(native("add-one").unwrap()(10), 11);
+        assert!(native("mruby-script").is_none());
+
+        let mut case = case(Phase::Execute, Runtime::Native);
+        assert!(supported(&case));
+        case.native = None;
+        assert!(!supported(&case));
+    }
+
+    #[test]
+    fn test_run() {
+        for &(phase, runtime) in &[
+            (Phase::Execute, Runtime::Native),
+            (Phase::Execute, Runtime::WasmerSinglepass),
+            (Phase::AotTotal, Runtime::Lucet),
+        ] {
+            let mut timer = Once::default();
+            run(&case(phase, runtime), WASM, &mut timer);
+            assert!(timer.0 > Duration::from_secs(0));
+        }
+    }
+
+    #[test]
+    fn test_artifact() {
+        for &runtime in &[Runtime::WasmerSinglepass, Runtime::Lucet] {
+            let key = compile_artifact(runtime, WASM).unwrap();
+            assert_eq!(run_artifact(runtime, &key, "run", &[10]), 89);
+        }
+        assert_eq!(compile_artifact(Runtime::Native, WASM), None);
+    }
+
+    #[test]
+    fn test_loop() {
+        let mut timer = Loop::new(3);
+        run(
+            &case(Phase::Compile, Runtime::WasmerSinglepass),
+            WASM,
+            &mut timer,
+        );
+        assert_eq!(timer.times.len(), 3);
+        assert!(timer.mean() > Duration::from_secs(0));
+    }
+}
@@ -0,0 +1,669 @@
+use crate::harness::output_dir;
+use crate::host::Fingerprint;
+use crate::matrix::{Phase, Runtime};
+use crate::table::{format_count, format_time};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// The measurement of results without criterion's estimates, and of criterion's
+// when BENCH_MEASUREMENT wasn't recorded
+pub const WALL: &str = "wall";
+
+fn wall() -> String {
+    WALL.to_owned()
+}
+
+// Whether means of `measurement` are times in ns, as wall-clock and CPU times
+// are, rather than counts of a software event
+pub fn is_time(measurement: &str) -> bool {
+    matches!(measurement, "" | WALL | "thread-cpu" | "process-cpu")
+}
+
+// A mean in the unit of its measurement, eg. `907.89 us` or `312.00 minor-faults`
+pub fn format_mean(measurement: &str, mean: f64) -> String {
+    if is_time(measurement) {
+        format_time(mean)
+    } else {
+        format!("{} {}", format_count(mean), measurement)
+    }
+}
+
+// Baselines a criterion run with `args` saves its estimates in, `new` always
+// and the one given with `--save-baseline`
+fn saved_baselines<S: AsRef<str>>(args: &[S]) -> Vec<String> {
+    let mut baselines = vec!["new".to_owned()];
+    let mut args = args.iter().map(AsRef::as_ref);
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--save-baseline=") {
+            baselines.push(name.to_owned());
+        } else if arg == "--save-baseline" || arg == "-s" {
+            baselines.extend(args.next().map(str::to_owned));
+        }
+    }
+    baselines
+}
+
+// Criterion's estimates don't say what was measured, so the benchmarks append
+// the BENCH_MEASUREMENT of each criterion group to
+// `target/wasm-bench/measurements.csv` as `baseline,group,measurement` for each
+// baseline they save. Bench binaries run with different measurements can save
+// to the same baseline, the group tells their results apart.
+pub fn record_measurement(group: &str, measurement: &str) {
+    let args: Vec<String> = std::env::args().collect();
+    let append = || -> io::Result<()> {
+        let dir = output_dir();
+        fs::create_dir_all(&dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("measurements.csv"))?;
+        for baseline in saved_baselines(&args) {
+            writeln!(file, "{},{},{}", baseline, group, measurement)?;
+        }
+        Ok(())
+    };
+    if let Err(e) = append() {
+        eprintln!("failed to record the measurement: {}", e);
+    }
+}
+
+// What a result is about. Criterion ids and the ids of our own csv files both
+// map to it, `compile_scaling` ones with the module size as input so they meet
+// the size criterion plots against.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Key {
+    pub phase: String,
+    pub sample: String,
+    pub runtime: String,
+    pub input: String,
+}
+
+impl Key {
+    pub fn parse(id: &str) -> Option<Self> {
+        let key = |phase: &str, sample: String, runtime: &str, input: &str| Key {
+            phase: phase.to_owned(),
+            sample,
+            runtime: runtime.to_owned(),
+            input: input.to_owned(),
+        };
+        let synth = |dimension: &str, runtime: &str, bytes: &str| {
+            key("compile", format!("synth-{}", dimension), runtime, bytes)
+        };
+        let parts: Vec<&str> = id.split('/').collect();
+        match parts.as_slice() {
+            // criterion's `compile_scaling_dimension/runtime/bytes`
+            [group, runtime, bytes] => Some(synth(
+                group.strip_prefix("compile_scaling_")?,
+                runtime,
+                bytes,
+            )),
+            ["compile_scaling", dimension, runtime, bytes] => {
+                Some(synth(dimension, runtime, bytes))
+            }
+            [phase, sample, runtime, input] => Some(key(phase, sample.to_string(), runtime, input)),
+            _ => None,
+        }
+    }
+
+    // `wasmer-cranelift` is runtime `wasmer` with backend `cranelift`
+    fn runtime(&self) -> (String, String) {
+        match self.runtime.parse::<Runtime>() {
+            Ok(runtime) => {
+                let backend = runtime.name()[runtime.engine().len()..].trim_start_matches('-');
+                (runtime.engine().to_owned(), backend.to_owned())
+            }
+            Err(_) => (self.runtime.clone(), String::new()),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}/{}",
+            self.phase, self.sample, self.runtime, self.input
+        )
+    }
+}
+
+// Everything known about one key. Estimates are in the unit of `measurement`,
+// ns for times and a count per iteration for software events.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Row {
+    pub runtime: String,
+    pub backend: String,
+    pub sample: String,
+    pub phase: String,
+    pub input: String,
+    // `measured` by criterion, or the status in outcomes.csv
+    pub status: String,
+    // BENCH_MEASUREMENT criterion ran with, `wall` for plain iterations
+    #[serde(default = "wall")]
+    pub measurement: String,
+    pub samples: Option<u64>,
+    pub iterations: Option<u64>,
+    pub mean: Option<f64>,
+    pub mean_lower: Option<f64>,
+    pub mean_upper: Option<f64>,
+    pub median: Option<f64>,
+    pub median_lower: Option<f64>,
+    pub median_upper: Option<f64>,
+    pub std_dev: Option<f64>,
+    pub wasm_bytes: Option<u64>,
+    pub artifact_bytes: Option<u64>,
+    pub peak_rss_bytes: Option<u64>,
+    pub heap_peak_bytes: Option<u64>,
+    pub allocations: Option<u64>,
+}
+
+const CSV_HEADER: &[&str] = &[
+    "runtime",
+    "backend",
+    "sample",
+    "phase",
+    "input",
+    "status",
+    "measurement",
+    "samples",
+    "iterations",
+    "mean",
+    "mean_lower",
+    "mean_upper",
+    "median",
+    "median_lower",
+    "median_upper",
+    "std_dev",
+    "wasm_bytes",
+    "artifact_bytes",
+    "peak_rss_bytes",
+    "heap_peak_bytes",
+    "allocations",
+];
+
+impl Row {
+    // The measurement, `wall` for rows made up without one
+    pub fn measured_as(&self) -> &str {
+        if self.measurement.is_empty() {
+            WALL
+        } else {
+            &self.measurement
+        }
+    }
+
+    pub fn key(&self) -> Key {
+        let runtime = if self.backend.is_empty() {
+            self.runtime.clone()
+        } else {
+            format!("{}-{}", self.runtime, self.backend)
+        };
+        Key {
+            phase: self.phase.clone(),
+            sample: self.sample.clone(),
+            runtime,
+            input: self.input.clone(),
+        }
+    }
+
+    fn fields(&self) -> Vec<String> {
+        fn opt<T: ToString>(v: Option<T>) -> String {
+            v.map(|v| v.to_string()).unwrap_or_default()
+        }
+        vec![
+            self.runtime.clone(),
+            self.backend.clone(),
+            self.sample.clone(),
+            self.phase.clone(),
+            self.input.clone(),
+            self.status.clone(),
+            self.measurement.clone(),
+            opt(self.samples),
+            opt(self.iterations),
+            opt(self.mean),
+            opt(self.mean_lower),
+            opt(self.mean_upper),
+            opt(self.median),
+            opt(self.median_lower),
+            opt(self.median_upper),
+            opt(self.std_dev),
+            opt(self.wasm_bytes),
+            opt(self.artifact_bytes),
+            opt(self.peak_rss_bytes),
+            opt(self.heap_peak_bytes),
+            opt(self.allocations),
+        ]
+    }
+}
+
+#[derive(Deserialize)]
+struct Benchmark {
+    group_id: String,
+    full_id: String,
+}
+
+#[derive(Deserialize)]
+struct Interval {
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    confidence_interval: Interval,
+    point_estimate: f64,
+}
+
+type Estimates = HashMap<String, Estimate>;
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&source).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Directories named `baseline` holding a benchmark.json, like criterion's own
+// report does it
+fn baselines(dir: &Path, baseline: &str, found: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if entry.file_name() == baseline && path.join("benchmark.json").exists() {
+            found.push(path);
+        } else {
+            baselines(&path, baseline, found);
+        }
+    }
+}
+
+// Lines of one of our csv files, split into the id and `fields` fields from
+// the right, as ids may contain commas
+fn csv_rows(path: &Path, fields: usize) -> Vec<(String, Vec<String>)> {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    source
+        .lines()
+        .filter_map(|line| {
+            let mut parts: Vec<String> = line.rsplitn(fields + 1, ',').map(String::from).collect();
+            if parts.len() != fields + 1 {
+                return None;
+            }
+            parts.reverse();
+            let id = parts.remove(0);
+            Some((id, parts))
+        })
+        .collect()
+}
+
+// Rows by key, with the host they were measured on
+#[derive(Default)]
+pub struct Results {
+    pub host: Option<Fingerprint>,
+    rows: BTreeMap<Key, Row>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    host: Option<Fingerprint>,
+    results: Vec<Row>,
+}
+
+impl Results {
+    fn entry(&mut self, key: Key) -> &mut Row {
+        self.rows.entry(key.clone()).or_insert_with(|| {
+            let (runtime, backend) = key.runtime();
+            Row {
+                runtime,
+                backend,
+                sample: key.sample,
+                phase: key.phase,
+                input: key.input,
+                measurement: wall(),
+                ..Row::default()
+            }
+        })
+    }
+
+    // Gathers criterion's estimates of `baseline`, `new` being the last run, and
+    // what the benchmarks appended to the csv files in `output_dir`. Those
+    // accumulate over runs, so an outcome only counts for keys criterion has no
+    // estimates of, and the last line of a key wins.
+    pub fn collect(
+        criterion_dir: &Path,
+        baseline: &str,
+        output_dir: &Path,
+    ) -> Result<Self, String> {
+        let mut results = Results::default();
+        let host = output_dir.join("host.json");
+        if host.exists() {
+            results.host = Some(Fingerprint::load(&host)?);
+        }
+
+        // The last measurement each group of `baseline` was run with
+        let measurements: BTreeMap<String, String> =
+            csv_rows(&output_dir.join("measurements.csv"), 2)
+                .into_iter()
+                .filter(|(id, _)| id == baseline)
+                .map(|(_, mut fields)| (fields.remove(0), fields.remove(0)))
+                .collect();
+
+        let mut dirs = vec![];
+        baselines(criterion_dir, baseline, &mut dirs);
+        for dir in dirs {
+            let benchmark: Benchmark = read_json(&dir.join("benchmark.json"))?;
+            let key = match Key::parse(&benchmark.full_id) {
+                Some(key) => key,
+                None => continue,
+            };
+            let estimates: Estimates = read_json(&dir.join("estimates.json"))?;
+            let (iters, _): (Vec<f64>, Vec<f64>) = read_json(&dir.join("sample.json"))?;
+
+            let result = results.entry(key);
+            result.status = "measured".to_owned();
+            result.measurement = measurements
+                .get(&benchmark.group_id)
+                .cloned()
+                .unwrap_or_else(wall);
+            result.samples = Some(iters.len() as u64);
+            result.iterations = Some(iters.iter().sum::<f64>() as u64);
+            if let Some(mean) = estimates.get("Mean") {
+                result.mean = Some(mean.point_estimate);
+                result.mean_lower = Some(mean.confidence_interval.lower_bound);
+                result.mean_upper = Some(mean.confidence_interval.upper_bound);
+            }
+            if let Some(median) = estimates.get("Median") {
+                result.median = Some(median.point_estimate);
+                result.median_lower = Some(median.confidence_interval.lower_bound);
+                result.median_upper = Some(median.confidence_interval.upper_bound);
+            }
+            result.std_dev = estimates.get("StdDev").map(|e| e.point_estimate);
+        }
+
+        for (id, fields) in csv_rows(&output_dir.join("outcomes.csv"), 3) {
+            let result = match Key::parse(&id) {
+                Some(key) => results.entry(key),
+                None => continue,
+            };
+            if result.status == "measured" {
+                continue;
+            }
+            result.status = fields[0].clone();
+            result.measurement = wall();
+            result.samples = None;
+            result.iterations = fields[1].parse().ok().filter(|n| *n > 0);
+            result.mean = match fields[0].as_str() {
+                "sampled" => fields[2].parse().ok(),
+                _ => None,
+            };
+        }
+
+        for (id, fields) in csv_rows(&output_dir.join("artifacts.csv"), 2) {
+            if let Some(key) = Key::parse(&id) {
+                let result = results.entry(key);
+                result.wasm_bytes = fields[0].parse().ok();
+                result.artifact_bytes = fields[1].parse().ok();
+            }
+        }
+
+        for (id, fields) in csv_rows(&output_dir.join("memory.csv"), 3) {
+            if let Some(key) = Key::parse(&id) {
+                let result = results.entry(key);
+                result.peak_rss_bytes = fields[0].parse().ok();
+                result.heap_peak_bytes = fields[1].parse().ok();
+                result.allocations = fields[2].parse().ok();
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub fn from_rows(rows: Vec<Row>) -> Self {
+        Results {
+            host: None,
+            rows: rows.into_iter().map(|r| (r.key(), r)).collect(),
+        }
+    }
+
+    // Reads back a results.json
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let document: Document = read_json(path)?;
+        let mut results = Self::from_rows(document.results);
+        results.host = document.host;
+        Ok(results)
+    }
+
+    // The row with the same key as `row`
+    pub fn find(&self, row: &Row) -> Option<&Row> {
+        self.rows.get(&row.key())
+    }
+
+    pub fn get(&self, phase: Phase, sample: &str, runtime: Runtime, input: &str) -> Option<&Row> {
+        self.rows.get(&Key {
+            phase: phase.to_string(),
+            sample: sample.to_owned(),
+            runtime: runtime.to_string(),
+            input: input.to_owned(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Row> {
+        self.rows.values()
+    }
+
+    pub fn to_json(&self) -> String {
+        let document = Document {
+            host: self.host.clone(),
+            results: self.iter().cloned().collect(),
+        };
+        serde_json::to_string_pretty(&document).unwrap()
+    }
+
+    // Each row ends with the host's fields. Fields with a comma, like the inputs
+    // of multiple arguments, are quoted.
+    pub fn to_csv(&self) -> String {
+        let quote = |field: String| {
+            if field.contains(',') || field.contains('"') {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        };
+        let host = self.host.clone().unwrap_or_default().fields();
+        let mut header = CSV_HEADER.to_vec();
+        header.extend(host.iter().map(|(name, _)| *name));
+        let mut csv = header.join(",") + "\n";
+        for result in self.iter() {
+            let mut fields = result.fields();
+            if self.host.is_some() {
+                fields.extend(host.iter().map(|(_, value)| value.clone()));
+            } else {
+                fields.extend(host.iter().map(|_| String::new()));
+            }
+            let fields: Vec<String> = fields.into_iter().map(quote).collect();
+            csv += &fields.join(",");
+            csv += "\n";
+        }
+        csv
+    }
+
+    // Writes `results.json` and `results.csv` into `dir`
+    pub fn write(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        fs::write(dir.join("results.json"), self.to_json()).map_err(|e| e.to_string())?;
+        fs::write(dir.join("results.csv"), self.to_csv()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ESTIMATES: &str = r#"{
+        "Mean": {"confidence_interval": {"confidence_level": 0.95, "lower_bound": 90.0, "upper_bound": 110.0}, "point_estimate": 100.0, "standard_error": 5.0},
+        "Median": {"confidence_interval": {"confidence_level": 0.95, "lower_bound": 95.0, "upper_bound": 99.0}, "point_estimate": 97.0, "standard_error": 1.0},
+        "StdDev": {"confidence_interval": {"confidence_level": 0.95, "lower_bound": 1.0, "upper_bound": 3.0}, "point_estimate": 2.0, "standard_error": 0.5}
+    }"#;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_key() {
+        let key = Key::parse("execute/fibonacci/wasmer-cranelift/10,20").unwrap();
+        assert_eq!(key.phase, "execute");
+        assert_eq!(key.input, "10,20");
+        assert_eq!(key.runtime(), ("wasmer".to_owned(), "cranelift".to_owned()));
+        assert_eq!(
+            Key::parse("compile_scaling_depth/lucet/1024"),
+            Key::parse("compile_scaling/depth/lucet/1024")
+        );
+        assert_eq!(
+            Key::parse("compile_scaling_depth/lucet/1024")
+                .unwrap()
+                .sample,
+            "synth-depth"
+        );
+        assert_eq!(Key::parse("execute/fibonacci"), None);
+    }
+
+    #[test]
+    fn test_collect() {
+        let root = std::env::temp_dir().join(format!("wasm-bench-export-{}", std::process::id()));
+        let criterion = root.join("criterion");
+        let output = root.join("wasm-bench");
+
+        let dir = criterion.join("execute_fibonacci/lucet/10/new");
+        write(
+            &dir.join("benchmark.json"),
+            r#"{"group_id": "execute/fibonacci", "full_id": "execute/fibonacci/lucet/10"}"#,
+        );
+        write(&dir.join("estimates.json"), ESTIMATES);
+        write(
+            &dir.join("sample.json"),
+            "[[1.0, 2.0, 3.0], [100.0, 200.0, 300.0]]",
+        );
+        write(
+            &output.join("outcomes.csv"),
+            "execute/fibonacci/lucet/10,timed_out,0,120000000000\n\
+             aot_compile/nbody/wasmer-llvm/10,sampled,3,5000\n",
+        );
+        write(
+            &output.join("artifacts.csv"),
+            "aot_compile/nbody/wasmer-llvm/10,2048,4096\n",
+        );
+        write(
+            &output.join("measurements.csv"),
+            "new,execute/fibonacci,thread-cpu\n\
+             minor-faults,execute/fibonacci,minor-faults\n\
+             new,execute/fibonacci,minor-faults\n\
+             new,lucet_pool/fibonacci,wall\n",
+        );
+        let host = Fingerprint {
+            cores: 8,
+            rustc: "rustc 1.43.0-nightly".to_owned(),
+            ..Fingerprint::default()
+        };
+        write(
+            &output.join("host.json"),
+            &serde_json::to_string(&host).unwrap(),
+        );
+
+        let results = Results::collect(&criterion, "new", &output).unwrap();
+        results.write(&output).unwrap();
+        let loaded = Results::load(&output.join("results.json")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            results.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.host, Some(host));
+        assert_eq!(
+            loaded
+                .get(Phase::Execute, "fibonacci", Runtime::Lucet, "10")
+                .and_then(|r| r.mean),
+            Some(100.0)
+        );
+        let results: Vec<&Row> = results.iter().collect();
+        assert_eq!(results.len(), 2);
+
+        let aot = results[0];
+        assert_eq!(
+            (
+                aot.runtime.as_str(),
+                aot.backend.as_str(),
+                aot.phase.as_str()
+            ),
+            ("wasmer", "llvm", "aot_compile")
+        );
+        assert_eq!(aot.status, "sampled");
+        assert_eq!(aot.measurement, "wall");
+        assert_eq!((aot.iterations, aot.mean), (Some(3), Some(5000.0)));
+        assert_eq!(
+            (aot.wasm_bytes, aot.artifact_bytes),
+            (Some(2048), Some(4096))
+        );
+
+        let execute = results[1];
+        assert_eq!(execute.status, "measured");
+        assert_eq!(execute.measurement, "minor-faults");
+        assert_eq!((execute.samples, execute.iterations), (Some(3), Some(6)));
+        assert_eq!(execute.mean, Some(100.0));
+        assert_eq!(
+            (execute.median_lower, execute.median_upper),
+            (Some(95.0), Some(99.0))
+        );
+        assert_eq!(execute.std_dev, Some(2.0));
+    }
+
+    #[test]
+    fn test_measurement() {
+        assert_eq!(saved_baselines(&["my_benchmark", "--bench"]), vec!["new"]);
+        assert_eq!(
+            saved_baselines(&["my_benchmark", "--save-baseline", "cpu", "compile"]),
+            vec!["new", "cpu"]
+        );
+        assert_eq!(
+            saved_baselines(&["my_benchmark", "--save-baseline=cpu"]),
+            vec!["new", "cpu"]
+        );
+        assert_eq!(format_mean("thread-cpu", 1500.0), "1.5000 us");
+        assert_eq!(format_mean("minor-faults", 312.0), "312.00 minor-faults");
+
+        let row: Row = serde_json::from_str(
+            r#"{"runtime": "lucet", "backend": "", "sample": "nbody", "phase": "execute", "input": "10", "status": "measured"}"#,
+        )
+        .unwrap();
+        assert_eq!(row.measurement, "wall");
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut results = Results::default();
+        results.entry(Key::parse("execute/fibonacci/rust-native/10,20").unwrap());
+        let csv = results.to_csv();
+        let mut lines = csv.lines();
+        let header = lines.next().unwrap();
+        assert!(header.starts_with("runtime,backend,sample,phase,input,"));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("rust-native,,fibonacci,execute,\"10,20\","));
+        // One more comma in the quoted input
+        assert_eq!(row.matches(',').count(), header.matches(',').count() + 1);
+    }
+}
@@ -0,0 +1,399 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_SECS: f64 = 120.0;
+// Only what is known to take minutes per iteration, LLVM is sampled by criterion
+// like the other backends
+const DEFAULT_SLOW: &str = "aot_compile/mruby-script";
+const DEFAULT_SLOW_ITERS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Plan {
+    Run,
+    Reduced(u32),
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome<T> {
+    Finished(T, Duration),
+    TimedOut(Duration),
+    Failed(String),
+}
+
+// Threads `run_with_timeout` gave up on which haven't returned yet. Anything
+// measured meanwhile shares the CPU and memory with them.
+static LEFTOVER: AtomicUsize = AtomicUsize::new(0);
+
+pub fn leftover() -> usize {
+    LEFTOVER.load(Ordering::SeqCst)
+}
+
+// Waits up to `timeout` for the leftover threads to return, false when some are
+// still running
+pub fn wait_for_leftover(timeout: Duration) -> bool {
+    let start = Instant::now();
+    while leftover() > 0 {
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+// Counts a thread given up on off the leftover once it returns or panics
+struct Abandoned(Arc<Mutex<bool>>);
+
+impl Drop for Abandoned {
+    fn drop(&mut self) {
+        if *self.0.lock().unwrap_or_else(PoisonError::into_inner) {
+            LEFTOVER.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// Runs `f` on a separate thread and gives up waiting after `timeout`. A timed out
+// thread cannot be cancelled, it keeps running in the background until it returns
+// and is counted in `leftover` until then.
+pub fn run_with_timeout<T, F>(timeout: Duration, f: F) -> Outcome<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let abandoned = Arc::new(Mutex::new(false));
+    let guard = Abandoned(abandoned.clone());
+    let start = Instant::now();
+    let handle = thread::spawn(move || {
+        let _guard = guard;
+        let v = f();
+        let _ = tx.send((v, start.elapsed()));
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok((v, elapsed)) => Outcome::Finished(v, elapsed),
+        Err(RecvTimeoutError::Timeout) => {
+            // Checked again under the lock, so a thread returning right now is
+            // either received here or counted off by its guard
+            let mut given_up = abandoned.lock().unwrap_or_else(PoisonError::into_inner);
+            match rx.try_recv() {
+                Ok((v, elapsed)) => Outcome::Finished(v, elapsed),
+                Err(TryRecvError::Disconnected) => {
+                    drop(given_up);
+                    Outcome::Failed(panic_message(handle.join()))
+                }
+                Err(TryRecvError::Empty) => {
+                    *given_up = true;
+                    LEFTOVER.fetch_add(1, Ordering::SeqCst);
+                    Outcome::TimedOut(timeout)
+                }
+            }
+        }
+        Err(RecvTimeoutError::Disconnected) => Outcome::Failed(panic_message(handle.join())),
+    }
+}
+
+fn panic_message(result: thread::Result<()>) -> String {
+    match result {
+        Ok(()) => "no result".to_owned(),
+        Err(payload) => match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => (*message).to_owned(),
+                Err(_) => "panicked".to_owned(),
+            },
+        },
+    }
+}
+
+// A pattern is either a single name matching any part of an id, eg. `wasmer-llvm`,
+// or a `group/sample/runtime` path where `*` matches anything and missing trailing
+// parts match everything, eg. `aot_compile/mruby-script`.
+fn matches(pattern: &str, id: &str) -> bool {
+    let parts: Vec<&str> = id.split('/').collect();
+    if !pattern.contains('/') {
+        return parts.contains(&pattern);
+    }
+
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    pattern.len() <= parts.len()
+        && pattern
+            .iter()
+            .zip(parts.iter())
+            .all(|(p, v)| *p == "*" || p == v)
+}
+
+fn patterns(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub struct Budget {
+    pub timeout: Duration,
+    pub slow_iters: u32,
+    skip: Vec<String>,
+    slow: Vec<String>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs_f64(DEFAULT_TIMEOUT_SECS),
+            slow_iters: DEFAULT_SLOW_ITERS,
+            skip: vec![],
+            slow: patterns(DEFAULT_SLOW),
+        }
+    }
+}
+
+impl Budget {
+    // BENCH_TIMEOUT   wall-clock budget in seconds for a single iteration
+    // BENCH_SKIP      comma separated patterns which are not run at all
+    // BENCH_SLOW      comma separated patterns which are run BENCH_SLOW_ITERS times
+    //                 with a plain timing loop instead of going through criterion
+    pub fn from_env() -> Self {
+        let mut budget = Self::default();
+        if let Some(secs) = std::env::var("BENCH_TIMEOUT")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+        {
+            budget.timeout = Duration::from_secs_f64(secs);
+        }
+        if let Ok(v) = std::env::var("BENCH_SKIP") {
+            budget.skip = patterns(&v);
+        }
+        if let Ok(v) = std::env::var("BENCH_SLOW") {
+            budget.slow = patterns(&v);
+        }
+        if let Some(n) = std::env::var("BENCH_SLOW_ITERS")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+        {
+            budget.slow_iters = n.max(1);
+        }
+        budget
+    }
+
+    pub fn skip(mut self, pattern: &str) -> Self {
+        self.skip.push(pattern.to_owned());
+        self
+    }
+
+    pub fn slow(mut self, pattern: &str) -> Self {
+        self.slow.push(pattern.to_owned());
+        self
+    }
+
+    pub fn plan(&self, id: &str) -> Plan {
+        if self.skip.iter().any(|p| matches(p, id)) {
+            Plan::Skip
+        } else if self.slow.iter().any(|p| matches(p, id)) {
+            Plan::Reduced(self.slow_iters)
+        } else {
+            Plan::Run
+        }
+    }
+
+    // Decides whether `id` should be handed over to criterion. Skipped and slow
+    // combinations are handled here and recorded, everything else gets a single
+    // probe iteration first so a hanging combination is reported instead of
+    // blocking the whole run. `routine` runs one iteration including its setup
+    // and returns the time spent in the measured part. Nothing is run while an
+    // iteration which timed out earlier is still running, after waiting a timeout
+    // for it `id` is recorded as left over instead.
+    pub fn admit<F>(&self, id: &str, routine: F) -> bool
+    where
+        F: Fn() -> Duration + Send + Sync + Clone + 'static,
+    {
+        let plan = self.plan(id);
+        if plan != Plan::Skip && !wait_for_leftover(self.timeout) {
+            record(id, &Record::Leftover);
+            return false;
+        }
+        match plan {
+            Plan::Skip => {
+                record(id, &Record::Skipped);
+                false
+            }
+            Plan::Reduced(iters) => {
+                let mut total = Duration::from_secs(0);
+                for _ in 0..iters {
+                    match run_with_timeout(self.timeout, routine.clone()) {
+                        Outcome::Finished(elapsed, _) => total += elapsed,
+                        Outcome::TimedOut(after) => {
+                            record(id, &Record::TimedOut(after));
+                            return false;
+                        }
+                        Outcome::Failed(message) => {
+                            record(id, &Record::Failed(message));
+                            return false;
+                        }
+                    }
+                }
+                record(id, &Record::Sampled(iters, total / iters));
+                false
+            }
+            Plan::Run => match run_with_timeout(self.timeout, routine) {
+                Outcome::Finished(_, _) => true,
+                Outcome::TimedOut(after) => {
+                    record(id, &Record::TimedOut(after));
+                    false
+                }
+                Outcome::Failed(message) => {
+                    record(id, &Record::Failed(message));
+                    false
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    Skipped,
+    // Not run, a timed out iteration was still running
+    Leftover,
+    TimedOut(Duration),
+    Failed(String),
+    Sampled(u32, Duration),
+}
+
+pub fn target_dir() -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"))
+}
+
+pub fn output_dir() -> PathBuf {
+    target_dir().join("wasm-bench")
+}
+
+fn record(id: &str, record: &Record) {
+    match record {
+        Record::Skipped => println!("{:<40} skipped", id),
+        Record::Leftover => println!(
+            "{:<40} not run, {} timed out iteration(s) still running",
+            id,
+            leftover()
+        ),
+        Record::TimedOut(after) => println!("{:<40} timed out after {:?}", id, after),
+        Record::Failed(message) => println!("{:<40} failed: {}", id, message),
+        Record::Sampled(iters, mean) => {
+            println!("{:<40} time: {:?} (mean of {} iterations)", id, mean, iters)
+        }
+    }
+
+    if let Err(e) = append_outcome(id, record) {
+        eprintln!("failed to record outcome for {}: {}", id, e);
+    }
+}
+
+// Outcomes that bypass criterion are appended to `target/wasm-bench/outcomes.csv`
+// as `id,status,iterations,mean_ns`.
+fn append_outcome(id: &str, record: &Record) -> io::Result<()> {
+    let dir = output_dir();
+    fs::create_dir_all(&dir)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("outcomes.csv"))?;
+
+    match record {
+        Record::Skipped => writeln!(file, "{},skipped,0,", id),
+        Record::Leftover => writeln!(file, "{},leftover,0,", id),
+        Record::TimedOut(after) => writeln!(file, "{},timed_out,0,{}", id, after.as_nanos()),
+        Record::Failed(_) => writeln!(file, "{},failed,0,", id),
+        Record::Sampled(iters, mean) => {
+            writeln!(file, "{},sampled,{},{}", id, iters, mean.as_nanos())
+        }
+    }
+}
+
+// Sizes of compiled artifacts are appended to `target/wasm-bench/artifacts.csv`
+// as `id,wasm_bytes,artifact_bytes`
+pub fn record_artifact(id: &str, wasm_bytes: u64, artifact_bytes: u64) {
+    println!(
+        "{:<40} artifact: {} bytes from {} bytes of wasm",
+        id, artifact_bytes, wasm_bytes
+    );
+
+    let append = || -> io::Result<()> {
+        let dir = output_dir();
+        fs::create_dir_all(&dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("artifacts.csv"))?;
+        writeln!(file, "{},{},{}", id, wasm_bytes, artifact_bytes)
+    };
+    if let Err(e) = append() {
+        eprintln!("failed to record artifact size for {}: {}", id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("wasmer-llvm", "jit/fibonacci/wasmer-llvm"));
+        assert!(matches(
+            "aot_compile/mruby-script",
+            "aot_compile/mruby-script/lucet"
+        ));
+        assert!(matches("*/*/lucet", "execute/nbody/lucet"));
+        assert!(!matches("*/*/lucet", "execute/nbody/wasmer-singlepass"));
+        assert!(!matches("wasmer", "jit/fibonacci/wasmer-llvm"));
+    }
+
+    #[test]
+    fn test_plan() {
+        let budget = Budget::default().skip("jit/mruby-script");
+        assert_eq!(budget.plan("jit/mruby-script/lucet"), Plan::Skip);
+        assert_eq!(
+            budget.plan("aot_compile/mruby-script/lucet"),
+            Plan::Reduced(DEFAULT_SLOW_ITERS)
+        );
+        assert_eq!(budget.plan("jit/fibonacci/wasmer-llvm"), Plan::Run);
+        assert_eq!(budget.plan("jit/fibonacci/wasmer-singlepass"), Plan::Run);
+        let budget = budget.slow("wasmer-llvm");
+        assert_eq!(
+            budget.plan("jit/fibonacci/wasmer-llvm"),
+            Plan::Reduced(DEFAULT_SLOW_ITERS)
+        );
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        match run_with_timeout(Duration::from_secs(5), || 42) {
+            Outcome::Finished(v, _) => assert_eq!(v, 42),
+            outcome => panic!("should finish, got {:?}", outcome),
+        }
+        let outcome: Outcome<()> = run_with_timeout(Duration::from_secs(5), || panic!("trap"));
+        assert_eq!(outcome, Outcome::Failed("trap".to_owned()));
+    }
+
+    // The only test timing out, others would see its thread in `leftover`
+    #[test]
+    fn test_leftover() {
+        let outcome = run_with_timeout(Duration::from_millis(10), || {
+            thread::sleep(Duration::from_millis(300))
+        });
+        assert_eq!(outcome, Outcome::TimedOut(Duration::from_millis(10)));
+        assert_eq!(leftover(), 1);
+        assert!(!wait_for_leftover(Duration::from_millis(10)));
+        assert!(wait_for_leftover(Duration::from_secs(5)));
+        assert_eq!(leftover(), 0);
+    }
+}
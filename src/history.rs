@@ -0,0 +1,391 @@
+use crate::export::{Results, Row};
+use crate::host::Fingerprint;
+use crate::samples;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_HISTORY: &str = "bench-history";
+
+// Crates whose versions decide what is being measured
+const RUNTIME_CRATES: &[&str] = &[
+    "wasmer-runtime",
+    "wasmer-runtime-core",
+    "wasmer-singlepass-backend",
+    "wasmer-clif-backend",
+    "wasmer-llvm-backend",
+    "lucet-runtime",
+    "lucetc",
+];
+
+// Saved runs are kept in BENCH_HISTORY, `bench-history` by default, as
+// `name.json`
+pub fn history_dir() -> PathBuf {
+    std::env::var_os("BENCH_HISTORY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_HISTORY))
+}
+
+// `git describe` of the working tree, `-dirty` when it has changes
+pub fn commit() -> Option<String> {
+    let output = Command::new("git")
+        .arg("describe")
+        .arg("--always")
+        .arg("--dirty")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+// Versions of the runtime crates in a Cargo.lock
+pub fn versions(lock: &str) -> BTreeMap<String, String> {
+    let lock: toml::Value = match toml::from_str(lock) {
+        Ok(lock) => lock,
+        Err(_) => return BTreeMap::new(),
+    };
+    let packages = lock.get("package").and_then(|p| p.as_array());
+    packages
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            let name = package.get("name")?.as_str()?;
+            let version = package.get("version")?.as_str()?;
+            if RUNTIME_CRATES.contains(&name) {
+                Some((name.to_owned(), version.to_owned()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// The versions this binary was built with, if its Cargo.lock is still around
+pub fn built_versions() -> BTreeMap<String, String> {
+    let lock = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.lock");
+    versions(&fs::read_to_string(lock).unwrap_or_default())
+}
+
+// FNV-1a of a module, enough to tell whether a sample was rebuilt between runs
+pub fn digest(wasm: &[u8]) -> String {
+    let hash = wasm.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+// Digests of the modules of the default samples
+pub fn sample_digests() -> BTreeMap<String, String> {
+    samples::load_default()
+        .unwrap_or_default()
+        .iter()
+        .map(|s| (s.name.clone(), digest(&s.wasm)))
+        .collect()
+}
+
+// Samples both have a digest of whose modules differ
+pub fn changed_samples(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<String> {
+    before
+        .iter()
+        .filter(|(name, digest)| matches!(after.get(*name), Some(d) if d != *digest))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Meta {
+    pub name: String,
+    pub commit: Option<String>,
+    // Seconds since the epoch
+    pub created: u64,
+    pub versions: BTreeMap<String, String>,
+    // Digests of the sample modules, empty in runs saved before they were kept
+    #[serde(default)]
+    pub samples: BTreeMap<String, String>,
+}
+
+impl Meta {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            commit: commit(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            versions: built_versions(),
+            samples: sample_digests(),
+        }
+    }
+}
+
+// Exported results with what they were measured on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub meta: Meta,
+    #[serde(default)]
+    pub host: Option<Fingerprint>,
+    pub results: Vec<Row>,
+}
+
+impl Run {
+    pub fn new(meta: Meta, results: &Results) -> Self {
+        Self {
+            meta,
+            host: results.host.clone(),
+            results: results.iter().cloned().collect(),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        if self.meta.name.is_empty() || self.meta.name.contains(['/', '\\']) {
+            return Err(format!("invalid name `{}`", self.meta.name));
+        }
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let path = dir.join(format!("{}.json", self.meta.name));
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+
+    pub fn load(dir: &Path, name: &str) -> Result<Self, String> {
+        let path = dir.join(format!("{}.json", name));
+        let json = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Every saved run, oldest first
+    pub fn list(dir: &Path) -> Result<Vec<Self>, String> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(vec![]),
+        };
+        let mut runs = vec![];
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension() != Some("json".as_ref()) {
+                continue;
+            }
+            let name = path.file_stem().unwrap().to_string_lossy();
+            runs.push(Self::load(dir, &name)?);
+        }
+        runs.sort_by_key(|r| r.meta.created);
+        Ok(runs)
+    }
+
+    pub fn results(&self) -> Results {
+        let mut results = Results::from_rows(self.results.clone());
+        results.host = self.host.clone();
+        results
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Regressed,
+    Improved,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub id: String,
+    // What both means are of, eg. `wall` or `minor-faults`
+    pub measurement: String,
+    pub baseline: f64,
+    pub current: f64,
+    // Relative change of the mean, `0.1` is 10% slower
+    pub change: f64,
+    pub verdict: Verdict,
+}
+
+// Compares the means of the results both have. A change counts when it is
+// beyond `threshold`, eg. `0.05` for 5%, and the confidence intervals don't
+// overlap. Results of a few plain iterations have no intervals, their means
+// are compared alone. Fails when a result was measured differently in both, eg.
+// in wall-clock time in one and CPU time in the other.
+pub fn compare(
+    baseline: &Results,
+    current: &Results,
+    threshold: f64,
+) -> Result<Vec<Comparison>, String> {
+    let mut comparisons = vec![];
+    for row in current.iter() {
+        let base = match baseline.find(row) {
+            Some(base) => base,
+            None => continue,
+        };
+        if base.measured_as() != row.measured_as() {
+            return Err(format!(
+                "{} was measured as {} in the baseline and as {} now",
+                row.key(),
+                base.measured_as(),
+                row.measured_as()
+            ));
+        }
+        let (before, after) = match (base.mean, row.mean) {
+            (Some(before), Some(after)) if before > 0.0 => (before, after),
+            _ => continue,
+        };
+        let change = after / before - 1.0;
+        let apart = |lower: Option<f64>, upper: Option<f64>| match (lower, upper) {
+            (Some(lower), Some(upper)) => lower > upper,
+            _ => true,
+        };
+        let verdict = if change > threshold && apart(row.mean_lower, base.mean_upper) {
+            Verdict::Regressed
+        } else if change < -threshold && apart(base.mean_lower, row.mean_upper) {
+            Verdict::Improved
+        } else {
+            Verdict::Unchanged
+        };
+        comparisons.push(Comparison {
+            id: row.key().to_string(),
+            measurement: row.measured_as().to_owned(),
+            baseline: before,
+            current: after,
+            change,
+            verdict,
+        });
+    }
+    Ok(comparisons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK: &str = r#"
+[[package]]
+name = "lucetc"
+version = "0.6.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "wasmer-runtime"
+version = "0.15.0"
+source = "git+https://github.com/wasmerio/wasmer?tag=0.15.0#5ab6e35672969d8b733efadf6c31b7ddd0f84f02"
+
+[[package]]
+name = "serde"
+version = "1.0.104"
+"#;
+
+    fn row(phase: &str, mean: f64, interval: Option<(f64, f64)>) -> Row {
+        Row {
+            runtime: "wasmer".to_owned(),
+            backend: "singlepass".to_owned(),
+            sample: "fibonacci".to_owned(),
+            phase: phase.to_owned(),
+            input: "10".to_owned(),
+            mean: Some(mean),
+            mean_lower: interval.map(|i| i.0),
+            mean_upper: interval.map(|i| i.1),
+            ..Row::default()
+        }
+    }
+
+    #[test]
+    fn test_versions() {
+        let versions = versions(LOCK);
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions["wasmer-runtime"], "0.15.0");
+        assert_eq!(versions["lucetc"], "0.6.1");
+        assert!(super::versions("not a lock").is_empty());
+    }
+
+    #[test]
+    fn test_compare() {
+        let baseline = Results::from_rows(vec![
+            row("compile", 100.0, Some((95.0, 105.0))),
+            row("execute", 100.0, Some((95.0, 105.0))),
+            row("instantiate", 100.0, Some((95.0, 105.0))),
+            row("aot_compile", 100.0, None),
+        ]);
+        let current = Results::from_rows(vec![
+            row("compile", 120.0, Some((115.0, 125.0))),
+            row("execute", 120.0, Some((100.0, 140.0))),
+            row("instantiate", 80.0, Some((75.0, 85.0))),
+            row("aot_compile", 103.0, None),
+            row("jit", 100.0, None),
+        ]);
+
+        let verdicts: BTreeMap<String, Verdict> = compare(&baseline, &current, 0.05)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.id, c.verdict))
+            .collect();
+        assert_eq!(verdicts.len(), 4);
+        assert_eq!(
+            verdicts["compile/fibonacci/wasmer-singlepass/10"],
+            Verdict::Regressed
+        );
+        // Overlapping intervals
+        assert_eq!(
+            verdicts["execute/fibonacci/wasmer-singlepass/10"],
+            Verdict::Unchanged
+        );
+        assert_eq!(
+            verdicts["instantiate/fibonacci/wasmer-singlepass/10"],
+            Verdict::Improved
+        );
+        // Below the threshold
+        assert_eq!(
+            verdicts["aot_compile/fibonacci/wasmer-singlepass/10"],
+            Verdict::Unchanged
+        );
+
+        let cpu = Results::from_rows(vec![Row {
+            measurement: "thread-cpu".to_owned(),
+            ..row("compile", 100.0, None)
+        }]);
+        let error = compare(&baseline, &cpu, 0.05).unwrap_err();
+        assert!(
+            error.contains("as wall in the baseline and as thread-cpu now"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_changed_samples() {
+        let digests = |pairs: &[(&str, &[u8])]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, wasm)| (name.to_string(), digest(wasm)))
+                .collect()
+        };
+        let before = digests(&[("fibonacci", b"old"), ("nbody", b"same"), ("loop", b"gone")]);
+        let after = digests(&[("fibonacci", b"new"), ("nbody", b"same")]);
+        assert_eq!(changed_samples(&before, &after), ["fibonacci"]);
+        assert!(changed_samples(&BTreeMap::new(), &after).is_empty());
+        assert_ne!(digest(b"old"), digest(b"new"));
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("wasm-bench-history-{}", std::process::id()));
+        let results = Results::from_rows(vec![row("compile", 100.0, None)]);
+        let mut meta = Meta::new("before");
+        meta.created = 1;
+        Run::new(meta, &results).save(&dir).unwrap();
+        Run::new(Meta::new("after"), &results).save(&dir).unwrap();
+        assert!(Run::new(Meta::new("../escape"), &results)
+            .save(&dir)
+            .is_err());
+
+        let runs = Run::list(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let names: Vec<&str> = runs.iter().map(|r| r.meta.name.as_str()).collect();
+        assert_eq!(names, ["before", "after"]);
+        assert_eq!(runs[0].results().iter().count(), 1);
+    }
+}
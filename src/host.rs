@@ -0,0 +1,165 @@
+use crate::harness::output_dir;
+use crate::history::built_versions;
+use crate::isolation::Isolation;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use wasmer_runtime::Backend;
+
+// What results were measured on, to tell whether two runs are comparable and
+// to reproduce them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub cpu: Option<String>,
+    pub cores: usize,
+    // cpufreq governor of the benchmark's core, eg. `performance`
+    pub governor: Option<String>,
+    pub kernel: Option<String>,
+    pub rustc: String,
+    // Versions of the runtime crates in Cargo.lock
+    pub crates: BTreeMap<String, String>,
+    // Wasmer backends compiled in
+    pub backends: Vec<String>,
+    #[serde(default)]
+    pub isolation: Isolation,
+}
+
+// The first `field: value` of /proc/cpuinfo
+fn cpuinfo(cpuinfo: &str, field: &str) -> Option<String> {
+    cpuinfo
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .find(|(name, _)| name.trim() == field)
+        .map(|(_, value)| value.trim().to_owned())
+}
+
+fn read(path: &str) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    Some(value.trim().to_owned())
+}
+
+// cpufreq governor of a core, None without cpufreq, eg. in most VMs
+pub fn governor(cpu: usize) -> Option<String> {
+    read(&format!(
+        "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor",
+        cpu
+    ))
+}
+
+impl Fingerprint {
+    pub fn current(isolation: Isolation) -> Self {
+        let info = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let kernel = match (
+            read("/proc/sys/kernel/ostype"),
+            read("/proc/sys/kernel/osrelease"),
+        ) {
+            (Some(os), Some(release)) => Some(format!("{} {}", os, release)),
+            _ => None,
+        };
+        Self {
+            // `model name` on x86, `Model` or `CPU part` elsewhere
+            cpu: cpuinfo(&info, "model name")
+                .or_else(|| cpuinfo(&info, "Model"))
+                .or_else(|| cpuinfo(&info, "CPU part")),
+            cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            governor: governor(isolation.cpu.unwrap_or(0)),
+            kernel,
+            rustc: env!("RUSTC_VERSION").to_owned(),
+            crates: built_versions(),
+            backends: Backend::variants()
+                .iter()
+                .filter(|b| **b != "auto")
+                .map(|b| b.to_string())
+                .collect(),
+            isolation,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Name and value of each field, as in the README and the csv export
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let unknown = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown".to_owned());
+        let crates: Vec<String> = self
+            .crates
+            .iter()
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect();
+        vec![
+            ("cpu", unknown(&self.cpu)),
+            ("cores", self.cores.to_string()),
+            ("governor", unknown(&self.governor)),
+            ("kernel", unknown(&self.kernel)),
+            ("rustc", self.rustc.clone()),
+            ("crates", crates.join("; ")),
+            ("backends", self.backends.join("/")),
+            (
+                "pinned",
+                self.isolation
+                    .cpu
+                    .map_or("no".to_owned(), |cpu| format!("cpu {}", cpu)),
+            ),
+            ("nice", self.isolation.nice.to_string()),
+        ]
+    }
+}
+
+// One line, eg. `Intel(R) Core(TM) i7-8559U x8, powersave, Linux 5.3.0, rustc ...,
+// lucetc 0.6.1; ..., singlepass/cranelift/llvm`
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = self.fields();
+        write!(f, "{} x{}", fields[0].1, fields[1].1)?;
+        for (_, value) in &fields[2..] {
+            if !value.is_empty() {
+                write!(f, ", {}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Written to `target/wasm-bench/host.json` when a benchmark starts, with the
+// isolation it runs with, and picked up from there by the export
+pub fn record(isolation: Isolation) {
+    let write = move || -> Result<(), String> {
+        let dir = output_dir();
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(&Fingerprint::current(isolation)).unwrap();
+        fs::write(dir.join("host.json"), json).map_err(|e| e.to_string())
+    };
+    if let Err(e) = write() {
+        eprintln!("failed to record the host: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpuinfo() {
+        let info = "processor\t: 0\nmodel name\t: Intel(R) Core(TM) i7-8559U CPU @ 2.70GHz\nflags\t\t: fpu";
+        assert_eq!(
+            cpuinfo(info, "model name").unwrap(),
+            "Intel(R) Core(TM) i7-8559U CPU @ 2.70GHz"
+        );
+        assert_eq!(cpuinfo(info, "Model"), None);
+    }
+
+    #[test]
+    fn test_current() {
+        let host = Fingerprint::current(Isolation::default());
+        assert!(host.cores >= 1);
+        assert!(host.rustc.starts_with("rustc "), "{}", host.rustc);
+        assert!(!host.backends.is_empty());
+
+        let line = host.to_string();
+        assert!(line.contains(&format!(" x{}, ", host.cores)), "{}", line);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::host;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::mem;
+
+// How the benchmark ran, recorded with the host
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Isolation {
+    // Core the benchmark thread is pinned to
+    pub cpu: Option<usize>,
+    // Nice value it runs with
+    pub nice: i32,
+}
+
+// Pins the calling thread, and the threads it spawns afterwards, to `cpu`
+pub fn pin(cpu: usize) -> io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no such cpu"));
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// Cores the calling thread may run on
+pub fn affinity() -> io::Result<Vec<usize>> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize)
+            .filter(|cpu| libc::CPU_ISSET(*cpu, &set))
+            .collect())
+    }
+}
+
+// On Linux the nice value is per thread, threads spawned afterwards inherit it.
+// Lowering it needs CAP_SYS_NICE or a matching RLIMIT_NICE.
+pub fn set_nice(nice: i32) -> io::Result<()> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub fn nice() -> i32 {
+    // -1 is a valid nice value as well as the error, which can't happen for
+    // the calling thread
+    unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) }
+}
+
+// BENCH_CPU   core to pin the benchmark thread to, eg. one isolated with
+//             `isolcpus`, not pinned by default
+// BENCH_NICE  nice value to run with, eg. `-20`, unchanged by default
+//
+// Settings which can't be applied are warned about and left out, as is a CPU
+// frequency governor other than `performance`. Returns what is in effect.
+pub fn apply() -> Isolation {
+    let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let invalid = |_| io::Error::from(io::ErrorKind::InvalidInput);
+
+    let cpu = env("BENCH_CPU").and_then(|v| {
+        let result = v.parse().map_err(invalid).and_then(|c| pin(c).map(|_| c));
+        result
+            .map_err(|e| eprintln!("warning: not pinning to cpu {}: {}", v, e))
+            .ok()
+    });
+    if let Some(v) = env("BENCH_NICE") {
+        if let Err(e) = v.parse().map_err(invalid).and_then(set_nice) {
+            eprintln!("warning: not setting nice value {}: {}", v, e);
+        }
+    }
+
+    match host::governor(cpu.unwrap_or(0)) {
+        Some(governor) if governor != "performance" => eprintln!(
+            "warning: cpu frequency governor is `{}`, times vary with the clock, \
+             use `cpupower frequency-set -g performance`",
+            governor
+        ),
+        _ => {}
+    }
+
+    Isolation { cpu, nice: nice() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // Each in a thread of its own, as both change the calling thread
+    #[test]
+    fn test_pin() {
+        thread::spawn(|| {
+            let cpu = *affinity().unwrap().last().unwrap();
+            pin(cpu).unwrap();
+            assert_eq!(affinity().unwrap(), [cpu]);
+            let child = thread::spawn(|| affinity().unwrap()).join().unwrap();
+            assert_eq!(child, [cpu]);
+            assert!(pin(libc::CPU_SETSIZE as usize).is_err());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_nice() {
+        thread::spawn(|| {
+            // Raising the value is always allowed
+            let nice = (nice() + 1).min(19);
+            set_nice(nice).unwrap();
+            assert_eq!(super::nice(), nice);
+        })
+        .join()
+        .unwrap();
+    }
+}
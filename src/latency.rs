@@ -0,0 +1,79 @@
+use std::ops::Range;
+use std::time::Duration;
+
+// Calls 1, 2-10, 11-100, ... up to `calls`, as indices
+pub fn decades(calls: usize) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let (mut start, mut end) = (0, 1);
+    while start < calls {
+        ranges.push(start..end.min(calls));
+        start = end;
+        end *= 10;
+    }
+    ranges
+}
+
+// `1`, `2-10`, ... as the calls are counted from 1
+pub fn label(range: &Range<usize>) -> String {
+    if range.len() == 1 {
+        (range.start + 1).to_string()
+    } else {
+        format!("{}-{}", range.start + 1, range.end)
+    }
+}
+
+// Mean latency of each decade of calls
+pub fn by_decade(latencies: &[Duration]) -> Vec<(Range<usize>, Duration)> {
+    decades(latencies.len())
+        .into_iter()
+        .map(|range| {
+            let sum: Duration = latencies[range.clone()].iter().sum();
+            let mean = sum / range.len() as u32;
+            (range, mean)
+        })
+        .collect()
+}
+
+// Nearest-rank percentile of sorted latencies, eg. `0.99` for p99
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decades() {
+        assert_eq!(decades(1000), vec![0..1, 1..10, 10..100, 100..1000]);
+        assert_eq!(decades(50), vec![0..1, 1..10, 10..50]);
+        assert!(decades(0).is_empty());
+        let labels: Vec<String> = decades(50).iter().map(label).collect();
+        assert_eq!(labels, ["1", "2-10", "11-50"]);
+    }
+
+    #[test]
+    fn test_by_decade() {
+        let mut latencies = vec![Duration::from_millis(7)];
+        latencies.extend((1..12).map(Duration::from_micros));
+        let means = by_decade(&latencies);
+        assert_eq!(means.len(), 3);
+        assert_eq!(means[0].1, Duration::from_millis(7));
+        assert_eq!(means[1].1, Duration::from_micros(5));
+        assert_eq!(means[2], (10..12, Duration::from_nanos(10_500)));
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_micros).collect();
+        assert_eq!(percentile(&sorted, 0.5), Duration::from_micros(50));
+        assert_eq!(percentile(&sorted, 0.99), Duration::from_micros(99));
+        assert_eq!(percentile(&sorted, 0.0), Duration::from_micros(1));
+        assert_eq!(percentile(&sorted[..1], 0.99), Duration::from_micros(1));
+        assert_eq!(percentile(&[], 0.5), Duration::default());
+    }
+}
@@ -1,2 +1,22 @@
+pub mod chart;
+pub mod cold;
+pub mod cpu;
+pub mod density;
+pub mod dispatch;
+pub mod export;
+pub mod harness;
+pub mod history;
+pub mod host;
+pub mod isolation;
+pub mod latency;
 pub mod lucet_runner;
+pub mod matrix;
+pub mod memory;
+pub mod perf;
+pub mod report;
+pub mod samples;
+pub mod synth;
+pub mod table;
+pub mod throughput;
+pub mod trap;
 pub mod wasmer_runner;
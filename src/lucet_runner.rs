@@ -1,12 +1,33 @@
-use lucet_runtime::{DlModule, InstanceHandle, Limits, MmapRegion, Region};
+use crate::trap::{Trap, TrapKind};
+use lucet_runtime::{DlModule, Error, InstanceHandle, Limits, MmapRegion, Region, TrapCode, Val};
 use lucetc::{Lucetc, LucetcOpts};
 use multibase::{encode, Base};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::sync::Arc;
+
+// Heap and stack of every instance
+fn limits() -> Limits {
+    Limits {
+        heap_memory_size: 8 * 1024 * 1024,
+        stack_size: 128 * 1024,
+        ..Limits::default()
+    }
+}
+
+// Names the compiled artifact after the whole module, small hand written modules
+// easily share their first bytes
+fn module_id(wasm_bytes: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(wasm_bytes);
+    encode(Base::Base58Btc, &hasher.finish().to_le_bytes())
+}
 
 // What if we don't save it to file, but in a memory array?
 // aka, what's the overhead for using file IO
 // aka, what's the performance for simulated JIT
 pub fn aot_c(wasm_bytes: &[u8]) -> String {
-    let moduleid = encode(Base::Base58Btc, &wasm_bytes[0..30]);
+    let moduleid = module_id(wasm_bytes);
 
     let path = format!("./tmp/lucet/{}", moduleid);
     let output_path = std::path::Path::new(&path);
@@ -18,19 +39,19 @@ pub fn aot_c(wasm_bytes: &[u8]) -> String {
     moduleid
 }
 
+// Size of the shared object `aot_c` writes
+pub fn artifact_size(wasm_bytes: &[u8]) -> u64 {
+    let moduleid = aot_c(wasm_bytes);
+    std::fs::metadata(format!("./tmp/lucet/{}", moduleid))
+        .unwrap()
+        .len()
+}
+
 pub fn aot_e(moduleid: &str, arg: u32) -> u32 {
     lucet_runtime::lucet_internal_ensure_linked();
     let dl_module = DlModule::load(format!("./tmp/lucet/{}", moduleid)).unwrap();
 
-    let region = MmapRegion::create(
-        1,
-        &Limits {
-            heap_memory_size: 8 * 1024 * 1024,
-            stack_size: 128 * 1024,
-            ..Limits::default()
-        },
-    )
-    .unwrap();
+    let region = MmapRegion::create(1, &limits()).unwrap();
 
     let mut instance = region.new_instance(dl_module).unwrap();
 
@@ -47,8 +68,14 @@ pub fn aot_t(wasm_bytes: &[u8], arg: u32) -> u32 {
     aot_e(&moduleid, arg)
 }
 
+// lucetc has no entry point for validation alone, it parses through
+// cranelift-wasm which is built on wasmparser
+pub fn validate(wasm_bytes: &[u8]) -> bool {
+    wasmparser::validate(wasm_bytes, None).is_ok()
+}
+
 pub fn compile(wasm_bytes: &[u8]) -> String {
-    let moduleid = encode(Base::Base58Btc, &wasm_bytes[0..30]);
+    let moduleid = module_id(wasm_bytes);
 
     let path = format!("./tmp/lucet/{}", moduleid);
     let output_path = std::path::Path::new(&path);
@@ -60,22 +87,20 @@ pub fn compile(wasm_bytes: &[u8]) -> String {
 }
 
 pub fn instantiate(moduleid: &str) -> InstanceHandle {
+    try_instantiate(moduleid).unwrap()
+}
+
+// Fails instead of panicking, eg. when there is no address space left for
+// another region
+pub fn try_instantiate(moduleid: &str) -> Result<InstanceHandle, Error> {
     // I wonder how much overhead it is to write and read through file.
     // How about changing it to memory
     lucet_runtime::lucet_internal_ensure_linked();
-    let dl_module = DlModule::load(format!("./tmp/lucet/{}", moduleid)).unwrap();
+    let dl_module = DlModule::load(format!("./tmp/lucet/{}", moduleid))?;
 
-    let region = MmapRegion::create(
-        1,
-        &Limits {
-            heap_memory_size: 8 * 1024 * 1024,
-            stack_size: 128 * 1024,
-            ..Limits::default()
-        },
-    )
-    .unwrap();
+    let region = MmapRegion::create(1, &limits())?;
 
-    region.new_instance(dl_module).unwrap()
+    region.new_instance(dl_module)
 }
 
 pub fn prepare(wasm_bytes: &[u8]) -> InstanceHandle {
@@ -92,18 +117,127 @@ pub fn execute(instance: &mut InstanceHandle, arg: u32) -> u32 {
         .as_u32()
 }
 
+// A module loaded once and a region with a slot per instance alive at a time,
+// for threads to instantiate from concurrently
+pub struct Shared {
+    module: Arc<DlModule>,
+    region: Arc<MmapRegion>,
+}
+
+impl Shared {
+    pub fn new(moduleid: &str, slots: usize) -> Self {
+        lucet_runtime::lucet_internal_ensure_linked();
+        Self {
+            module: DlModule::load(format!("./tmp/lucet/{}", moduleid)).unwrap(),
+            region: MmapRegion::create(slots, &limits()).unwrap(),
+        }
+    }
+
+    // Takes a free slot, which is given back when the instance is dropped
+    pub fn instantiate(&self) -> InstanceHandle {
+        self.region.new_instance(self.module.clone()).unwrap()
+    }
+}
+
+// Instances of one module from a region with all its slots made up front.
+// Instances given back are reset and handed out again, instead of giving the
+// slot back to the region.
+pub struct Pool {
+    shared: Shared,
+    idle: Vec<InstanceHandle>,
+}
+
+impl Pool {
+    pub fn new(moduleid: &str, slots: usize) -> Self {
+        Self {
+            shared: Shared::new(moduleid, slots),
+            idle: vec![],
+        }
+    }
+
+    // An idle instance, or a new one from a free slot
+    pub fn take(&mut self) -> InstanceHandle {
+        self.idle.pop().unwrap_or_else(|| self.shared.instantiate())
+    }
+
+    // Resets the heap and globals to their initial state
+    pub fn give(&mut self, mut instance: InstanceHandle) {
+        instance.reset().unwrap();
+        self.idle.push(instance);
+    }
+
+    pub fn idle(&self) -> usize {
+        self.idle.len()
+    }
+}
+
+// Calls any export taking i32 arguments. Lucet doesn't know the return type, so
+// the result is whatever is left in the return register for void exports.
+pub fn call(instance: &mut InstanceHandle, export: &str, args: &[u32]) -> u32 {
+    try_call(instance, export, args).unwrap()
+}
+
+// The kind of a fault from its trap code
+fn trap_kind(code: TrapCode) -> TrapKind {
+    match code {
+        TrapCode::Unreachable => TrapKind::Unreachable,
+        TrapCode::IntegerDivByZero => TrapKind::DivideByZero,
+        TrapCode::IntegerOverflow => TrapKind::IntegerOverflow,
+        TrapCode::HeapOutOfBounds | TrapCode::OutOfBounds => TrapKind::OutOfBounds,
+        TrapCode::IndirectCallToNull | TrapCode::BadSignature | TrapCode::TableOutOfBounds => {
+            TrapKind::IndirectCall
+        }
+        TrapCode::StackOverflow => TrapKind::StackOverflow,
+        TrapCode::BadConversionToInteger | TrapCode::Interrupt => TrapKind::Other,
+    }
+}
+
+// Same as `call`, with the trap as an error. The kind of a fault is its trap
+// code, the rest of its details are addresses. Faults without a code and other
+// errors are classified by their message.
+pub fn try_call(instance: &mut InstanceHandle, export: &str, args: &[u32]) -> Result<u32, Trap> {
+    let args: Vec<Val> = args.iter().map(|a| (*a).into()).collect();
+    match instance.run(export, &args).and_then(|r| r.returned()) {
+        Ok(v) => Ok(v.as_u32()),
+        Err(e) => Err(match &e {
+            Error::RuntimeFault(details) => match details.trapcode {
+                Some(code) => Trap::with_kind(trap_kind(code), e.to_string()),
+                None => Trap::new(e.to_string()),
+            },
+            _ => Trap::new(e.to_string()),
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     static WASM: &'static [u8] = include_bytes!("../wasm-sample/fibonacci.wasm");
 
+    #[test]
+    fn test_module_id() {
+        assert_eq!(module_id(&WASM), module_id(&WASM));
+        assert_ne!(module_id(&WASM[..40]), module_id(&WASM[..41]));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&WASM));
+        assert!(!validate(&WASM[..100]));
+    }
+
     #[test]
     fn test_aot_c() {
         let moduleid = aot_c(&WASM);
         println!("moduleid is {:?}", moduleid);
     }
 
+    #[test]
+    fn test_artifact_size() {
+        assert!(artifact_size(&WASM) > 0);
+    }
+
     #[test]
     fn test_aot_t() {
         assert_eq!(aot_t(&WASM, 10), 89);
@@ -114,4 +248,54 @@ mod tests {
         let mut instance = prepare(&WASM);
         assert_eq!(execute(&mut instance, 10), 89);
     }
+
+    #[test]
+    fn test_call() {
+        let mut instance = prepare(&WASM);
+        assert_eq!(call(&mut instance, "run", &[10]), 89);
+    }
+
+    #[test]
+    fn test_try_call() {
+        let wasm = wat::parse_file("wasm-sample/traps/out-of-bounds.wat").unwrap();
+        let mut instance = prepare(&wasm);
+        let trap = try_call(&mut instance, "run", &[65536]).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::OutOfBounds, "{}", trap);
+        let mut instance = prepare(&wasm);
+        assert_eq!(try_call(&mut instance, "run", &[0]), Ok(0));
+
+        // Lucet tells integer division by zero and overflow apart
+        for sample in crate::samples::load_traps().unwrap() {
+            let mut instance = prepare(&sample.wasm);
+            let trap = try_call(&mut instance, &sample.export, &sample.args).unwrap_err();
+            assert_eq!(trap.kind, sample.trap.unwrap(), "{}: {}", sample.name, trap);
+        }
+    }
+
+    #[test]
+    fn test_shared() {
+        let shared = Arc::new(Shared::new(&compile(&WASM), 2));
+        let mut instance = shared.instantiate();
+        let other = shared.clone();
+        let v = std::thread::spawn(move || execute(&mut other.instantiate(), 10))
+            .join()
+            .unwrap();
+        assert_eq!(v, 89);
+        assert_eq!(execute(&mut instance, 10), 89);
+    }
+
+    #[test]
+    fn test_pool() {
+        let mut pool = Pool::new(&compile(&WASM), 2);
+        let mut first = pool.take();
+        let mut second = pool.take();
+        assert_eq!(execute(&mut first, 10), 89);
+        pool.give(first);
+        pool.give(second);
+        assert_eq!(pool.idle(), 2);
+
+        second = pool.take();
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(execute(&mut second, 10), 89);
+    }
 }
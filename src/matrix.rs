@@ -0,0 +1,436 @@
+use crate::samples::Sample;
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use wasmer_runtime::Backend;
+
+const DEFAULT_CONFIG: &str = "bench.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Runtime {
+    Native,
+    WasmerSinglepass,
+    WasmerCranelift,
+    WasmerLlvm,
+    Lucet,
+}
+
+impl Runtime {
+    pub const ALL: &'static [Runtime] = &[
+        Runtime::Native,
+        Runtime::WasmerSinglepass,
+        Runtime::WasmerCranelift,
+        Runtime::WasmerLlvm,
+        Runtime::Lucet,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Runtime::Native => "rust-native",
+            Runtime::WasmerSinglepass => "wasmer-singlepass",
+            Runtime::WasmerCranelift => "wasmer-cranelift",
+            Runtime::WasmerLlvm => "wasmer-llvm",
+            Runtime::Lucet => "lucet",
+        }
+    }
+
+    // The runtime without its compiler backend, eg. `wasmer` for all of Wasmer's
+    pub fn engine(self) -> &'static str {
+        match self {
+            Runtime::Native => "rust-native",
+            Runtime::WasmerSinglepass | Runtime::WasmerCranelift | Runtime::WasmerLlvm => "wasmer",
+            Runtime::Lucet => "lucet",
+        }
+    }
+
+    pub fn backend(self) -> Option<Backend> {
+        match self {
+            Runtime::WasmerSinglepass => Some(Backend::Singlepass),
+            Runtime::WasmerCranelift => Some(Backend::Cranelift),
+            Runtime::WasmerLlvm => Some(Backend::LLVM),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Runtime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Runtime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Runtime::ALL
+            .iter()
+            .find(|r| r.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown runtime `{}`", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Validate,
+    Compile,
+    Instantiate,
+    Execute,
+    Jit,
+    AotCompile,
+    AotExecute,
+    AotTotal,
+}
+
+impl Phase {
+    pub const ALL: &'static [Phase] = &[
+        Phase::Validate,
+        Phase::Compile,
+        Phase::Instantiate,
+        Phase::Execute,
+        Phase::Jit,
+        Phase::AotCompile,
+        Phase::AotExecute,
+        Phase::AotTotal,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Validate => "validate",
+            Phase::Compile => "compile",
+            Phase::Instantiate => "instantiate",
+            Phase::Execute => "execute",
+            Phase::Jit => "jit",
+            Phase::AotCompile => "aot_compile",
+            Phase::AotExecute => "aot_execute",
+            Phase::AotTotal => "aot_total",
+        }
+    }
+
+    // Native code is only compared on pure execution, and Lucet is not designed
+    // for JIT.
+    pub fn supports(self, runtime: Runtime) -> bool {
+        match (self, runtime) {
+            (Phase::Execute, _) => true,
+            (_, Runtime::Native) => false,
+            (Phase::Jit, Runtime::Lucet) => false,
+            _ => true,
+        }
+    }
+
+    pub fn takes_input(self) -> bool {
+        !matches!(
+            self,
+            Phase::Validate | Phase::Compile | Phase::Instantiate | Phase::AotCompile
+        )
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Phase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Phase::ALL
+            .iter()
+            .find(|p| p.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown phase `{}`", s))
+    }
+}
+
+// One benchmark of the matrix, identified as `phase/sample/runtime/args` where
+// multiple arguments are joined by `,`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub phase: Phase,
+    pub sample: String,
+    pub runtime: Runtime,
+    pub export: String,
+    pub args: Vec<u32>,
+    pub native: Option<String>,
+}
+
+impl Case {
+    pub fn input(&self) -> String {
+        let args: Vec<String> = self.args.iter().map(u32::to_string).collect();
+        args.join(",")
+    }
+
+    pub fn id(&self) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.phase,
+            self.sample,
+            self.runtime,
+            self.input()
+        )
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Config {
+    runtimes: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    phases: Option<Vec<String>>,
+    inputs: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    pub runtimes: Option<Vec<Runtime>>,
+    pub samples: Option<Vec<String>>,
+    pub phases: Option<Vec<Phase>>,
+    pub inputs: Option<Vec<u32>>,
+}
+
+fn parse_all<T: FromStr<Err = String>>(names: &[String]) -> Result<Vec<T>, String> {
+    names.iter().map(|n| n.parse()).collect()
+}
+
+fn split(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+impl Selection {
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(source).map_err(|e| e.to_string())?;
+        Ok(Self {
+            runtimes: config.runtimes.as_deref().map(parse_all).transpose()?,
+            samples: config.samples,
+            phases: config.phases.as_deref().map(parse_all).transpose()?,
+            inputs: config.inputs,
+        })
+    }
+
+    // Reads `bench.toml` (or the file in BENCH_CONFIG) when present, then lets
+    // BENCH_RUNTIMES, BENCH_SAMPLES, BENCH_PHASES and BENCH_INPUTS override it.
+    // Each variable is a comma separated list, eg. BENCH_PHASES=compile,execute.
+    pub fn from_env() -> Result<Self, String> {
+        let path = std::env::var("BENCH_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG.to_owned());
+        let mut selection = if Path::new(&path).exists() {
+            let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            Self::from_toml(&source).map_err(|e| format!("{}: {}", path, e))?
+        } else {
+            Self::default()
+        };
+
+        if let Ok(v) = std::env::var("BENCH_RUNTIMES") {
+            selection.runtimes = Some(parse_all(&split(&v))?);
+        }
+        if let Ok(v) = std::env::var("BENCH_SAMPLES") {
+            selection.samples = Some(split(&v));
+        }
+        if let Ok(v) = std::env::var("BENCH_PHASES") {
+            selection.phases = Some(parse_all(&split(&v))?);
+        }
+        if let Ok(v) = std::env::var("BENCH_INPUTS") {
+            selection.inputs = Some(
+                split(&v)
+                    .iter()
+                    .map(|n| n.parse().map_err(|_| format!("invalid input `{}`", n)))
+                    .collect::<Result<_, String>>()?,
+            );
+        }
+        Ok(selection)
+    }
+
+    pub fn runtimes(&self) -> Vec<Runtime> {
+        self.runtimes
+            .clone()
+            .unwrap_or_else(|| Runtime::ALL.to_vec())
+    }
+
+    pub fn phases(&self) -> Vec<Phase> {
+        self.phases.clone().unwrap_or_else(|| Phase::ALL.to_vec())
+    }
+
+    // Samples are selected by name or by size class, eg. `small`
+    pub fn has_sample(&self, sample: &Sample) -> bool {
+        match &self.samples {
+            Some(samples) => samples
+                .iter()
+                .any(|n| *n == sample.name || n == sample.size.name()),
+            None => true,
+        }
+    }
+}
+
+pub struct Matrix<'a> {
+    pub samples: &'a [Sample],
+    pub selection: Selection,
+}
+
+impl<'a> Matrix<'a> {
+    pub fn new(samples: &'a [Sample], selection: Selection) -> Self {
+        Self { samples, selection }
+    }
+
+    pub fn sample(&self, name: &str) -> Option<&'a Sample> {
+        self.samples.iter().find(|s| s.name == name)
+    }
+
+    pub fn cases(&self, phase: Phase) -> Vec<Case> {
+        let runtimes = self.selection.runtimes();
+        let mut cases = vec![];
+
+        for sample in self.samples {
+            if !sample.in_phase(phase) || !self.selection.has_sample(sample) {
+                continue;
+            }
+            // Selected inputs replace the arguments of single argument exports,
+            // otherwise execution is swept over the sample's own inputs
+            let inputs = match &self.selection.inputs {
+                Some(inputs) if phase.takes_input() && sample.args.len() == 1 => {
+                    inputs.iter().map(|i| vec![*i]).collect()
+                }
+                None if phase == Phase::Execute && !sample.inputs.is_empty() => {
+                    sample.inputs.iter().map(|i| vec![*i]).collect()
+                }
+                _ => vec![sample.args.clone()],
+            };
+            for &runtime in runtimes.iter().filter(|r| phase.supports(**r)) {
+                for args in &inputs {
+                    cases.push(Case {
+                        phase,
+                        sample: sample.name.clone(),
+                        runtime,
+                        export: sample.export.clone(),
+                        args: args.clone(),
+                        native: sample.native.clone(),
+                    });
+                }
+            }
+        }
+        cases
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samples::SizeClass;
+
+    fn samples() -> Vec<Sample> {
+        let mut mruby = Sample::new("mruby-script", b"\0asm").only(&[Phase::AotCompile]);
+        mruby.size = SizeClass::Large;
+        vec![Sample::new("fibonacci", b"\0asm"), mruby]
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let selection = Selection::from_toml(
+            r#"
+            runtimes = ["wasmer-singlepass", "lucet"]
+            phases = ["execute"]
+            inputs = [10, 20]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            selection.runtimes,
+            Some(vec![Runtime::WasmerSinglepass, Runtime::Lucet])
+        );
+        assert_eq!(selection.phases, Some(vec![Phase::Execute]));
+        assert_eq!(selection.samples, None);
+        assert_eq!("validate".parse(), Ok(Phase::Validate));
+        assert!(Selection::from_toml("runtimes = [\"wasmer\"]").is_err());
+    }
+
+    #[test]
+    fn test_cases() {
+        let samples = samples();
+        let matrix = Matrix::new(&samples, Selection::default());
+
+        let jit = matrix.cases(Phase::Jit);
+        assert!(jit.iter().all(|c| c.sample == "fibonacci"));
+        assert!(jit
+            .iter()
+            .all(|c| c.runtime != Runtime::Lucet && c.runtime != Runtime::Native));
+        assert_eq!(jit.len(), 3);
+
+        let aot_compile = matrix.cases(Phase::AotCompile);
+        assert_eq!(aot_compile.len(), 8);
+        assert_eq!(
+            aot_compile[0].id(),
+            "aot_compile/fibonacci/wasmer-singlepass/10"
+        );
+    }
+
+    #[test]
+    fn test_cases_with_selection() {
+        let samples = samples();
+        let selection = Selection {
+            runtimes: Some(vec![Runtime::Native, Runtime::Lucet]),
+            inputs: Some(vec![10, 20]),
+            ..Selection::default()
+        };
+        let matrix = Matrix::new(&samples, selection);
+
+        let ids: Vec<String> = matrix.cases(Phase::Execute).iter().map(Case::id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "execute/fibonacci/rust-native/10",
+                "execute/fibonacci/rust-native/20",
+                "execute/fibonacci/lucet/10",
+                "execute/fibonacci/lucet/20",
+            ]
+        );
+        assert_eq!(matrix.cases(Phase::Compile).len(), 1);
+    }
+
+    #[test]
+    fn test_cases_with_inputs() {
+        let mut samples = samples();
+        samples[0].inputs = vec![10, 20, 30];
+        let selection = Selection {
+            runtimes: Some(vec![Runtime::Lucet]),
+            ..Selection::default()
+        };
+        let matrix = Matrix::new(&samples, selection.clone());
+
+        let ids: Vec<String> = matrix.cases(Phase::Execute).iter().map(Case::id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "execute/fibonacci/lucet/10",
+                "execute/fibonacci/lucet/20",
+                "execute/fibonacci/lucet/30",
+            ]
+        );
+        // Only execution is swept
+        assert_eq!(matrix.cases(Phase::AotTotal).len(), 1);
+
+        let selection = Selection {
+            inputs: Some(vec![15]),
+            ..selection
+        };
+        let matrix = Matrix::new(&samples, selection);
+        assert_eq!(matrix.cases(Phase::Execute)[0].args, vec![15]);
+    }
+
+    #[test]
+    fn test_cases_by_size() {
+        let samples = samples();
+        let selection = Selection {
+            samples: Some(vec!["small".to_owned()]),
+            ..Selection::default()
+        };
+        let matrix = Matrix::new(&samples, selection);
+        let cases = matrix.cases(Phase::AotCompile);
+        assert_eq!(cases.len(), 4);
+        assert!(cases.iter().all(|c| c.sample == "fibonacci"));
+    }
+}
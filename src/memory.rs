@@ -0,0 +1,219 @@
+use crate::dispatch::{black_box, Timer};
+use crate::harness::output_dir;
+use crate::table::format_size;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+// Counts heap allocations of the whole process on top of the system allocator.
+// Only binaries which measure nothing but memory install it, with
+// `#[global_allocator] static ALLOC: CountingAlloc = CountingAlloc;`, as the
+// counting adds to every allocation a timed routine makes.
+pub struct CountingAlloc;
+
+fn grow(bytes: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    let current = CURRENT.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+fn shrink(bytes: usize) {
+    CURRENT.fetch_sub(bytes, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            if new_size > layout.size() {
+                grow(new_size - layout.size());
+            } else {
+                shrink(layout.size() - new_size);
+            }
+        }
+        new
+    }
+}
+
+// Whether `CountingAlloc` is the global allocator, nothing gets counted otherwise
+pub fn counting() -> bool {
+    ALLOCATIONS.load(Ordering::Relaxed) > 0
+}
+
+// A field of /proc/self/status in bytes, eg. `VmRSS`
+fn status(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with(field))?;
+    let kb: u64 = line[field.len() + 1..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+// Current RSS of the process, Linux only
+pub fn rss() -> Option<u64> {
+    status("VmRSS")
+}
+
+// Virtual address space the process has mapped, guard pages and reservations
+// included, Linux only
+pub fn virtual_size() -> Option<u64> {
+    status("VmSize")
+}
+
+// Resets the peak RSS of the process to its current RSS and returns it. Linux
+// only, None when /proc isn't there or writable.
+fn reset_peak_rss() -> Option<u64> {
+    fs::write("/proc/self/clear_refs", "5").ok()?;
+    status("VmRSS")
+}
+
+// What running something took in memory. Both peaks are relative to what was in
+// use before, and cover every thread of the process.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub peak_rss: Option<u64>,
+    pub heap_peak: Option<u64>,
+    pub allocations: u64,
+}
+
+// Formats as `peak RSS / peak heap`, eg. `12 M / 3.4 M`
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = |v: Option<u64>| v.map(format_size).unwrap_or_else(|| "NA".to_owned());
+        write!(f, "{} / {}", size(self.peak_rss), size(self.heap_peak))
+    }
+}
+
+pub fn measure<T, F: FnOnce() -> T>(f: F) -> (T, Usage) {
+    let rss = reset_peak_rss();
+    let heap = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(heap, Ordering::Relaxed);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+
+    let out = f();
+
+    // Before reading /proc, which allocates
+    let heap_peak = if counting() {
+        Some(PEAK.load(Ordering::Relaxed).saturating_sub(heap) as u64)
+    } else {
+        None
+    };
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) as u64;
+    let usage = Usage {
+        peak_rss: rss.and_then(|rss| Some(status("VmHWM")?.saturating_sub(rss))),
+        heap_peak,
+        allocations,
+    };
+    (out, usage)
+}
+
+// Measures one call of the routine after a warm up call, so one-off
+// initialisation of the runtime isn't counted
+#[derive(Debug, Default)]
+pub struct Footprint(pub Usage);
+
+impl Timer for Footprint {
+    fn iter<O, R: FnMut() -> O>(&mut self, mut routine: R) {
+        black_box(routine());
+        let (out, usage) = measure(&mut routine);
+        black_box(out);
+        self.0 = usage;
+    }
+}
+
+// Usages are appended to `target/wasm-bench/memory.csv` as
+// `id,peak_rss_bytes,heap_peak_bytes,allocations`
+pub fn record(id: &str, usage: &Usage) {
+    let append = || -> io::Result<()> {
+        let dir = output_dir();
+        fs::create_dir_all(&dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("memory.csv"))?;
+        let field = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_default();
+        writeln!(
+            file,
+            "{},{},{},{}",
+            id,
+            field(usage.peak_rss),
+            field(usage.heap_peak),
+            usage.allocations
+        )
+    };
+    if let Err(e) = append() {
+        eprintln!("failed to record memory usage for {}: {}", id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    #[test]
+    fn test_status() {
+        assert!(status("VmRSS").unwrap() > 0);
+        assert!(status("VmHWM").unwrap() > 0);
+        assert_eq!(status("Missing"), None);
+        assert!(virtual_size().unwrap() >= rss().unwrap());
+    }
+
+    #[test]
+    fn test_measure() {
+        let size = 16 * 1024 * 1024;
+        let (len, usage) = measure(|| {
+            let v = vec![1u8; size];
+            black_box(v).len()
+        });
+        assert_eq!(len, size);
+        assert!(usage.heap_peak.unwrap() >= size as u64);
+        assert!(usage.allocations >= 1);
+        if let Some(rss) = usage.peak_rss {
+            assert!(rss >= size as u64 / 2);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let usage = Usage {
+            peak_rss: Some(12 * 1024 * 1024),
+            heap_peak: None,
+            allocations: 0,
+        };
+        assert_eq!(usage.to_string(), "12 M / NA");
+    }
+}
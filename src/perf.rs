@@ -0,0 +1,191 @@
+use std::fmt;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::str::FromStr;
+
+const PERF_TYPE_SOFTWARE: u32 = 1;
+// Size of the first version of `perf_event_attr`, which has all we need
+const PERF_ATTR_SIZE_VER0: u32 = 64;
+const INHERIT: u64 = 1 << 1;
+const EXCLUDE_KERNEL: u64 = 1 << 5;
+const EXCLUDE_HV: u64 = 1 << 6;
+
+#[repr(C)]
+#[derive(Default)]
+struct Attr {
+    kind: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+// Software events of the kernel, counted without hardware counters, so they
+// work in VMs too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    PageFaults,
+    MinorFaults,
+    MajorFaults,
+    ContextSwitches,
+    CpuMigrations,
+}
+
+impl Event {
+    pub const ALL: &'static [Event] = &[
+        Event::PageFaults,
+        Event::MinorFaults,
+        Event::MajorFaults,
+        Event::ContextSwitches,
+        Event::CpuMigrations,
+    ];
+
+    // Same names as `perf stat -e`
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::PageFaults => "page-faults",
+            Event::MinorFaults => "minor-faults",
+            Event::MajorFaults => "major-faults",
+            Event::ContextSwitches => "context-switches",
+            Event::CpuMigrations => "cpu-migrations",
+        }
+    }
+
+    // PERF_COUNT_SW_* in linux/perf_event.h
+    fn config(self) -> u64 {
+        match self {
+            Event::PageFaults => 2,
+            Event::ContextSwitches => 3,
+            Event::CpuMigrations => 4,
+            Event::MinorFaults => 5,
+            Event::MajorFaults => 6,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Event {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Event::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown event `{}`", s))
+    }
+}
+
+// A counter of `event` for the calling thread and the threads it spawns after
+// opening it
+#[derive(Debug)]
+pub struct Counter {
+    pub event: Event,
+    fd: RawFd,
+}
+
+fn open(attr: &Attr) -> io::Result<RawFd> {
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            attr as *const Attr,
+            0,  // this thread
+            -1, // on any cpu
+            -1, // without a group
+            0,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fd as RawFd)
+}
+
+impl Counter {
+    // Counts the kernel's work, like page faults, when perf_event_paranoid lets
+    // us and only userspace otherwise
+    pub fn open(event: Event) -> io::Result<Self> {
+        let mut attr = Attr {
+            kind: PERF_TYPE_SOFTWARE,
+            size: PERF_ATTR_SIZE_VER0,
+            config: event.config(),
+            flags: INHERIT,
+            ..Attr::default()
+        };
+        let fd = open(&attr).or_else(|_| {
+            attr.flags |= EXCLUDE_KERNEL | EXCLUDE_HV;
+            open(&attr)
+        })?;
+        Ok(Self { event, fd })
+    }
+
+    pub fn read(&self) -> u64 {
+        let mut count = 0u64;
+        let n = unsafe { libc::read(self.fd, &mut count as *mut u64 as *mut libc::c_void, 8) };
+        assert_eq!(n, 8, "failed to read {}", self.event);
+        count
+    }
+}
+
+impl Drop for Counter {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event() {
+        for event in Event::ALL {
+            assert_eq!(event.name().parse(), Ok(*event));
+        }
+        assert!("cycles".parse::<Event>().is_err());
+    }
+
+    #[test]
+    fn test_counter() {
+        // Not every machine allows perf events, eg. containers without
+        // CAP_PERFMON or perf_event_paranoid above 2
+        let counter = match Counter::open(Event::MinorFaults) {
+            Ok(counter) => counter,
+            Err(e) => {
+                eprintln!("skipped: {}", e);
+                return;
+            }
+        };
+
+        // Fresh pages, the allocator may hand out ones which already faulted
+        let pages = 64;
+        let len = pages * 4096;
+        let start = counter.read();
+        unsafe {
+            let ptr = libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(ptr, libc::MAP_FAILED);
+            std::ptr::write_bytes(ptr as *mut u8, 1, len);
+            libc::munmap(ptr, len);
+        }
+        assert!(counter.read() - start >= pages as u64 / 2);
+    }
+}
@@ -0,0 +1,779 @@
+use crate::chart::{self, Axis, Point, Series, Unit};
+use crate::export::{format_mean, is_time, Results, Row, WALL};
+use crate::matrix::{Phase, Runtime};
+use crate::samples::Sample;
+use crate::synth::Dimension;
+use crate::table::{format_size, Table};
+
+const WASM_RUNTIMES: &[Runtime] = &[
+    Runtime::WasmerSinglepass,
+    Runtime::WasmerCranelift,
+    Runtime::WasmerLlvm,
+    Runtime::Lucet,
+];
+
+// Columns of the individual tables, lettered as the steps in the README
+const INDIVIDUAL: &[(&str, Phase)] = &[
+    ("a. parse", Phase::Validate),
+    ("ab. compile", Phase::Compile),
+    ("c. instantiate", Phase::Instantiate),
+    ("d. execute", Phase::Execute),
+];
+
+// The README's tables by the name of their marker, `<!-- report: name -->`, and
+// the heading and formula they are printed under on their own
+const TABLES: &[(&str, &str, &str)] = &[
+    ("host", "### Host", ""),
+    (
+        "wasmer-singlepass",
+        "### Individual - Wasmer/Singlepass",
+        "",
+    ),
+    ("wasmer-cranelift", "### Individual - Wasmer/Cranelift", ""),
+    ("wasmer-llvm", "### Individual - Wasmer/LLVM", ""),
+    ("lucet", "### Individual - Lucet", ""),
+    ("jit", "### Comparison - JIT", "a+b+c+d"),
+    ("aot_total", "#### AOT total", "a+b+b'+c'+c+d"),
+    ("aot_compile", "#### AOT compile (time)", "a+b+b'"),
+    ("aot_space", "#### AOT compile (space)", "b'"),
+    (
+        "compile_scaling",
+        "#### Compile scaling (time)",
+        "ab of generated modules",
+    ),
+    (
+        "compile_scaling_space",
+        "#### Compile scaling (space)",
+        "b' of generated modules",
+    ),
+    ("aot_execute", "#### AOT execution", "c'+c+d"),
+    ("execute", "### Comparison - Pure execution", "d"),
+    (
+        "break_even",
+        "### Break-even",
+        "(ab+c of the faster - ab+c of the other) / (d of the other - d of the faster)",
+    ),
+];
+
+// Charts of compile cost against execute speed, by their title and the phases
+// on their x and y axes
+const SCATTERS: &[(&str, Phase, Phase)] = &[
+    ("JIT: compile vs execute", Phase::Compile, Phase::Execute),
+    (
+        "AOT: compile vs execute",
+        Phase::AotCompile,
+        Phase::AotExecute,
+    ),
+];
+
+// `wasmer/singlepass` as in the README's headers, `native` for rust-native
+fn column(runtime: Runtime) -> String {
+    match runtime {
+        Runtime::Native => "native".to_owned(),
+        _ => runtime.name().replacen('-', "/", 1),
+    }
+}
+
+// A mean in the unit of its measurement, `~` when it comes from a few plain
+// iterations instead of criterion's sampling, `NA` for combinations which
+// aren't supported and `/` for ones without a result
+fn time(row: Option<&Row>, phase: Phase, runtime: Runtime) -> String {
+    if !phase.supports(runtime) {
+        return "NA".to_owned();
+    }
+    let row = match row {
+        Some(row) => row,
+        None => return "/".to_owned(),
+    };
+    match (row.status.as_str(), row.mean) {
+        ("measured", Some(mean)) => format_mean(row.measured_as(), mean),
+        ("sampled", Some(mean)) => format!("~{}", format_mean(row.measured_as(), mean)),
+        ("timed_out", _) => "timed out".to_owned(),
+        ("leftover", _) => "left over".to_owned(),
+        ("failed", _) => "failed".to_owned(),
+        _ => "/".to_owned(),
+    }
+}
+
+// Mean of a measured or sampled result
+fn measured(row: &Row) -> Option<f64> {
+    match row.status.as_str() {
+        "measured" | "sampled" => row.mean,
+        _ => None,
+    }
+}
+
+// Calls after which the runtime which executes faster has made up for a longer
+// compile and instantiate, given the `(setup, execute)` times of both: 0 when its
+// setup is no slower, None when it never does
+pub fn break_even(faster: (f64, f64), other: (f64, f64)) -> Option<u64> {
+    let (setup, execute) = (faster.0 - other.0, other.1 - faster.1);
+    if setup <= 0.0 {
+        Some(0)
+    } else if execute <= 0.0 {
+        None
+    } else {
+        Some((setup / execute).ceil() as u64)
+    }
+}
+
+pub struct Report<'a> {
+    results: &'a Results,
+    samples: &'a [Sample],
+    // What criterion measured, the unit of the charts
+    measurement: &'a str,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(results: &'a Results, samples: &'a [Sample]) -> Self {
+        let measurement = results
+            .iter()
+            .find(|r| r.status == "measured")
+            .map_or(WALL, Row::measured_as);
+        Self {
+            results,
+            samples,
+            measurement,
+        }
+    }
+
+    fn unit(&self) -> Unit {
+        if is_time(self.measurement) {
+            Unit::Nanos
+        } else {
+            Unit::Count
+        }
+    }
+
+    // Means of another measurement than the report's are left out, as they
+    // can't be put next to each other
+    fn measured(&self, row: &Row) -> Option<f64> {
+        if row.measured_as() == self.measurement {
+            measured(row)
+        } else {
+            None
+        }
+    }
+
+    // Tables are at the samples' default arguments
+    fn get(&self, phase: Phase, sample: &Sample, runtime: Runtime) -> Option<&Row> {
+        let args: Vec<String> = sample.args.iter().map(u32::to_string).collect();
+        self.results
+            .get(phase, &sample.name, runtime, &args.join(","))
+    }
+
+    fn mean(&self, phase: Phase, sample: &Sample, runtime: Runtime) -> Option<f64> {
+        self.measured(self.get(phase, sample, runtime)?)
+    }
+
+    // Sizes of the generated modules of `dimension` with a result, which
+    // `compile_scaling` exports with their size as input
+    fn synth_sizes(&self, dimension: Dimension) -> Vec<u64> {
+        let sample = format!("synth-{}", dimension);
+        let mut sizes: Vec<u64> = self
+            .results
+            .iter()
+            .filter(|r| r.sample == sample && r.phase == Phase::Compile.name())
+            .filter_map(|r| r.input.parse().ok())
+            .collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+        sizes
+    }
+
+    fn synth(&self, dimension: Dimension, bytes: u64, runtime: Runtime) -> Option<&Row> {
+        self.results.get(
+            Phase::Compile,
+            &format!("synth-{}", dimension),
+            runtime,
+            &bytes.to_string(),
+        )
+    }
+
+    // The compile time, or artifact size with `space`, of a generated module
+    fn synth_value(
+        &self,
+        dimension: Dimension,
+        bytes: u64,
+        runtime: Runtime,
+        space: bool,
+    ) -> Option<f64> {
+        let row = self.synth(dimension, bytes, runtime)?;
+        if space {
+            row.artifact_bytes.map(|b| b as f64)
+        } else {
+            self.measured(row)
+        }
+    }
+
+    // Samples with a result in any of the cells, the others would be a row of `/`
+    fn table<F>(&self, header: &[String], cells: F) -> Table
+    where
+        F: Fn(&Sample) -> Vec<String>,
+    {
+        let mut table = Table::new(header);
+        for sample in self.samples {
+            let row = cells(sample);
+            if row.iter().any(|c| c != "/" && c != "NA") {
+                let mut cells = vec![sample.name.clone()];
+                cells.extend(row);
+                table.row(&cells);
+            }
+        }
+        table
+    }
+
+    fn individual(&self, runtime: Runtime) -> Table {
+        let mut header = vec![String::new()];
+        header.extend(INDIVIDUAL.iter().map(|(name, _)| name.to_string()));
+        self.table(&header, |sample| {
+            INDIVIDUAL
+                .iter()
+                .map(|&(_, phase)| time(self.get(phase, sample, runtime), phase, runtime))
+                .collect()
+        })
+    }
+
+    fn comparison(&self, phase: Phase, runtimes: &[Runtime]) -> Table {
+        let mut header = vec![String::new()];
+        header.extend(runtimes.iter().map(|r| column(*r)));
+        self.table(&header, |sample| {
+            runtimes
+                .iter()
+                .map(|&runtime| time(self.get(phase, sample, runtime), phase, runtime))
+                .collect()
+        })
+    }
+
+    fn space(&self) -> Table {
+        let mut header = vec![String::new(), "source".to_owned()];
+        header.extend(WASM_RUNTIMES.iter().map(|r| column(*r)));
+        let mut table = Table::new(&header);
+        for sample in self.samples {
+            let sizes: Vec<String> = WASM_RUNTIMES
+                .iter()
+                .map(|&runtime| {
+                    self.get(Phase::AotCompile, sample, runtime)
+                        .and_then(|r| r.artifact_bytes)
+                        .map(format_size)
+                        .unwrap_or_else(|| "/".to_owned())
+                })
+                .collect();
+            if sizes.iter().any(|s| s != "/") {
+                let mut cells = vec![sample.name.clone(), format_size(sample.wasm.len() as u64)];
+                cells.extend(sizes);
+                table.row(&cells);
+            }
+        }
+        table
+    }
+
+    // A row per generated module, by dimension and size
+    fn scaling(&self, space: bool) -> Table {
+        let mut header = vec![String::new(), "module".to_owned()];
+        header.extend(WASM_RUNTIMES.iter().map(|r| column(*r)));
+        let mut table = Table::new(&header);
+        for &dimension in Dimension::ALL {
+            for bytes in self.synth_sizes(dimension) {
+                let mut cells = vec![dimension.to_string(), format_size(bytes)];
+                cells.extend(WASM_RUNTIMES.iter().map(|&runtime| {
+                    let row = self.synth(dimension, bytes, runtime);
+                    if space {
+                        row.and_then(|r| r.artifact_bytes)
+                            .map(format_size)
+                            .unwrap_or_else(|| "/".to_owned())
+                    } else {
+                        time(row, Phase::Compile, runtime)
+                    }
+                }));
+                if cells[2..].iter().any(|c| c != "/") {
+                    table.row(&cells);
+                }
+            }
+        }
+        table
+    }
+
+    // Each pair of runtimes with compile, instantiate and execute results, the
+    // faster executing first
+    fn break_even(&self) -> Table {
+        let mut table = Table::new(&["", "faster", "than", "break-even (calls)"]);
+        for sample in self.samples {
+            let costs: Vec<(Runtime, f64, f64)> = WASM_RUNTIMES
+                .iter()
+                .filter_map(|&runtime| {
+                    let mean = |phase| self.mean(phase, sample, runtime);
+                    let setup = mean(Phase::Compile)? + mean(Phase::Instantiate)?;
+                    Some((runtime, setup, mean(Phase::Execute)?))
+                })
+                .collect();
+            for (i, a) in costs.iter().enumerate() {
+                for b in &costs[i + 1..] {
+                    let (faster, other) = if a.2 <= b.2 { (a, b) } else { (b, a) };
+                    let calls = match break_even((faster.1, faster.2), (other.1, other.2)) {
+                        Some(0) => "always".to_owned(),
+                        Some(calls) => calls.to_string(),
+                        None => "never".to_owned(),
+                    };
+                    table.row(&[
+                        sample.name.clone(),
+                        column(faster.0),
+                        column(other.0),
+                        calls,
+                    ]);
+                }
+            }
+        }
+        table
+    }
+
+    // What the results were measured on, empty for results from before it was
+    // recorded
+    fn host(&self) -> Table {
+        let mut table = Table::new(&["", "host"]);
+        for (name, value) in self.results.host.iter().flat_map(|h| h.fields()) {
+            table.row(&[name.to_owned(), value]);
+        }
+        table.row(&["measurement", self.measurement]);
+        table
+    }
+
+    pub fn render_table(&self, name: &str) -> Option<Table> {
+        let table = match name {
+            "host" => self.host(),
+            "wasmer-singlepass" => self.individual(Runtime::WasmerSinglepass),
+            "wasmer-cranelift" => self.individual(Runtime::WasmerCranelift),
+            "wasmer-llvm" => self.individual(Runtime::WasmerLlvm),
+            "lucet" => self.individual(Runtime::Lucet),
+            "jit" => self.comparison(Phase::Jit, WASM_RUNTIMES),
+            "aot_total" => self.comparison(Phase::AotTotal, WASM_RUNTIMES),
+            "aot_compile" => self.comparison(Phase::AotCompile, WASM_RUNTIMES),
+            "aot_space" => self.space(),
+            "compile_scaling" => self.scaling(false),
+            "compile_scaling_space" => self.scaling(true),
+            "aot_execute" => self.comparison(Phase::AotExecute, WASM_RUNTIMES),
+            "execute" => self.comparison(Phase::Execute, Runtime::ALL),
+            "break_even" => self.break_even(),
+            _ => return None,
+        };
+        Some(table)
+    }
+
+    // All tables under their headings
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, heading, formula) in TABLES {
+            out += &format!("{}\n\n", heading);
+            if !formula.is_empty() {
+                out += &format!("> {}\n\n", formula);
+            }
+            out += &self.render_table(name).unwrap().render();
+            out += "\n";
+        }
+        out
+    }
+
+    // Times of a phase per sample and runtime, None without any
+    fn bars(&self, phase: Phase) -> Option<String> {
+        let runtimes: Vec<Runtime> = Runtime::ALL
+            .iter()
+            .copied()
+            .filter(|r| phase.supports(*r))
+            .collect();
+        let samples: Vec<&Sample> = self
+            .samples
+            .iter()
+            .filter(|s| runtimes.iter().any(|r| self.mean(phase, s, *r).is_some()))
+            .collect();
+        let groups: Vec<String> = samples.iter().map(|s| s.name.clone()).collect();
+        let series: Vec<Series> = runtimes
+            .iter()
+            .map(|&runtime| Series {
+                name: column(runtime),
+                values: samples
+                    .iter()
+                    .map(|s| self.mean(phase, s, runtime))
+                    .collect(),
+            })
+            .collect();
+        chart::bars(&groups, &series, self.unit())
+    }
+
+    // A point per sample and runtime with both results
+    fn scatter(&self, x: Phase, y: Phase) -> Option<String> {
+        let names: Vec<String> = WASM_RUNTIMES.iter().map(|r| column(*r)).collect();
+        let mut points = vec![];
+        for (i, &runtime) in WASM_RUNTIMES.iter().enumerate() {
+            for sample in self.samples {
+                let (x, y) = match (self.mean(x, sample, runtime), self.mean(y, sample, runtime)) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => continue,
+                };
+                points.push(Point {
+                    label: sample.name.clone(),
+                    series: i,
+                    x,
+                    y,
+                });
+            }
+        }
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let axis = |phase: Phase| Axis {
+            title: phase.name(),
+            unit: self.unit(),
+        };
+        chart::scatter(&names, &points, axis(x), axis(y))
+    }
+
+    // Compile time, or artifact size with `space`, of the generated modules of
+    // `dimension` against their size, a line per runtime
+    fn scaling_chart(&self, dimension: Dimension, space: bool) -> Option<String> {
+        let names: Vec<String> = WASM_RUNTIMES.iter().map(|r| column(*r)).collect();
+        let mut points = vec![];
+        for (i, &runtime) in WASM_RUNTIMES.iter().enumerate() {
+            for bytes in self.synth_sizes(dimension) {
+                if let Some(y) = self.synth_value(dimension, bytes, runtime, space) {
+                    points.push(Point {
+                        label: format!("{}, {}", names[i], format_size(bytes)),
+                        series: i,
+                        x: bytes as f64,
+                        y,
+                    });
+                }
+            }
+        }
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let x = Axis {
+            title: "module size",
+            unit: Unit::Bytes,
+        };
+        let y = if space {
+            Axis {
+                title: "artifact size",
+                unit: Unit::Bytes,
+            }
+        } else {
+            Axis {
+                title: "compile",
+                unit: self.unit(),
+            }
+        };
+        chart::lines(&names, &points, x, y)
+    }
+
+    // A static page of charts, a log-scale bar chart per phase, compile cost
+    // against execute speed and the compile cost of generated modules against
+    // their size, leaving out those without results
+    pub fn html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>wasm-runtime-benchmark</title>\n\
+             <style>body { font-family: sans-serif; margin: 2em; }</style>\n\
+             </head>\n<body>\n<h1>wasm-runtime-benchmark</h1>\n",
+        );
+        if let Some(host) = &self.results.host {
+            out += &format!("<p>{}</p>\n", chart::escape(&host.to_string()));
+        }
+        out += &format!("<p>measurement: {}</p>\n", chart::escape(self.measurement));
+        for &phase in Phase::ALL {
+            if let Some(svg) = self.bars(phase) {
+                out += &format!("<h2>{}</h2>\n{}", phase.name(), svg);
+            }
+        }
+        for (title, x, y) in SCATTERS {
+            if let Some(svg) = self.scatter(*x, *y) {
+                out += &format!("<h2>{}</h2>\n{}", chart::escape(title), svg);
+            }
+        }
+        for &dimension in Dimension::ALL {
+            for &(space, what) in &[(false, "compile time"), (true, "artifact size")] {
+                if let Some(svg) = self.scaling_chart(dimension, space) {
+                    out += &format!("<h2>{} by {}</h2>\n{}", what, dimension, svg);
+                }
+            }
+        }
+        out += "</body>\n</html>\n";
+        out
+    }
+
+    // Replaces the table below each `<!-- report: name -->` marker in `markdown`,
+    // blank lines between them included, leaving everything else as is. What
+    // follows the new table is kept a blank line apart from it.
+    pub fn update(&self, markdown: &str) -> Result<String, String> {
+        let mut out = String::new();
+        let mut lines = markdown.lines().peekable();
+        while let Some(line) = lines.next() {
+            out += line;
+            out += "\n";
+            let name = match line
+                .trim()
+                .strip_prefix("<!-- report:")
+                .and_then(|l| l.strip_suffix("-->"))
+            {
+                Some(name) => name.trim(),
+                None => continue,
+            };
+            let table = self
+                .render_table(name)
+                .ok_or_else(|| format!("unknown table `{}`", name))?;
+            while lines.next_if(|l| l.trim().is_empty()).is_some() {}
+            while lines.next_if(|l| l.starts_with('|')).is_some() {}
+            out += &table.render();
+            if matches!(lines.peek(), Some(l) if !l.trim().is_empty()) {
+                out += "\n";
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::Results;
+
+    fn results() -> Results {
+        let json = r#"{"host": {"cpu": "Intel(R) Core(TM) i7-8559U CPU @ 2.70GHz", "cores": 8, "governor": null, "kernel": "Linux 5.3.0", "rustc": "rustc 1.43.0-nightly", "crates": {"lucetc": "0.6.1"}, "backends": ["singlepass", "llvm"]}, "results": [
+            {"runtime": "wasmer", "backend": "singlepass", "sample": "add-one", "phase": "compile", "input": "10", "status": "measured", "mean": 907890.0},
+            {"runtime": "wasmer", "backend": "llvm", "sample": "add-one", "phase": "aot_compile", "input": "10", "status": "sampled", "mean": 1148400000.0, "wasm_bytes": 2150, "artifact_bytes": 13312},
+            {"runtime": "lucet", "backend": "", "sample": "add-one", "phase": "aot_compile", "input": "10", "status": "timed_out"},
+            {"runtime": "rust-native", "backend": "", "sample": "add-one", "phase": "execute", "input": "10", "status": "measured", "mean": 0.7022}
+        ]}"#;
+        let path =
+            std::env::temp_dir().join(format!("wasm-bench-report-{}.json", std::process::id()));
+        std::fs::write(&path, json).unwrap();
+        let results = Results::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        results
+    }
+
+    fn samples() -> Vec<Sample> {
+        vec![
+            Sample::new("add-one", &[0; 2150]),
+            Sample::new("fibonacci", &[]),
+        ]
+    }
+
+    #[test]
+    fn test_tables() {
+        let (results, samples) = (results(), samples());
+        let report = Report::new(&results, &samples);
+        assert_eq!(
+            report.render_table("wasmer-singlepass").unwrap().render(),
+            "\
+|         | a. parse | ab. compile | c. instantiate | d. execute |
+| ------- | -------- | ----------- | -------------- | ---------- |
+| add-one | /        | 907.89 us   | /              | /          |
+"
+        );
+        assert_eq!(
+            report.render_table("aot_compile").unwrap().render(),
+            "\
+|         | wasmer/singlepass | wasmer/cranelift | wasmer/llvm | lucet     |
+| ------- | ----------------- | ---------------- | ----------- | --------- |
+| add-one | /                 | /                | ~1.1484 s   | timed out |
+"
+        );
+        assert!(report.render_table("aot_space").unwrap().render().contains(
+            "| add-one | 2.1 K  | /                 | /                | 13 K        | /     |"
+        ));
+        assert!(report
+            .render_table("execute")
+            .unwrap()
+            .render()
+            .contains("| add-one | 702.20 ps |"));
+        assert!(report.render_table("jit").unwrap().is_empty());
+        assert!(report
+            .render_table("host")
+            .unwrap()
+            .render()
+            .contains("| backends    | singlepass/llvm "));
+        assert!(report
+            .render_table("host")
+            .unwrap()
+            .render()
+            .contains("| measurement | wall "));
+        assert!(report.render_table("missing").is_none());
+    }
+
+    #[test]
+    fn test_update() {
+        let (results, samples) = (results(), samples());
+        let report = Report::new(&results, &samples);
+        let readme = "\
+# Report
+
+<!-- report: aot_compile -->
+| old | table |
+| --- | ----- |
+
+`~` means an estimation.
+";
+        let updated = report.update(readme).unwrap();
+        assert!(
+            updated.starts_with("# Report\n\n<!-- report: aot_compile -->\n|         | wasmer/")
+        );
+        assert!(!updated.contains("old"));
+        assert!(updated.ends_with("timed out |\n\n`~` means an estimation.\n"));
+        assert!(report.update("<!-- report: nobody -->\n").is_err());
+
+        let readme = "\
+<!-- report: aot_compile -->
+
+| old | table |
+| --- | ----- |
+`~` means an estimation.
+
+<!-- report: jit -->
+
+### Next
+";
+        let updated = report.update(readme).unwrap();
+        assert!(!updated.contains("old"));
+        assert!(updated.starts_with("<!-- report: aot_compile -->\n|         | wasmer/"));
+        assert!(
+            updated.contains("timed out |\n\n`~` means an estimation.\n\n<!-- report: jit -->\n|")
+        );
+        assert!(updated.ends_with("| ----- |\n\n### Next\n"), "{}", updated);
+        assert_eq!(report.update(&updated).unwrap(), updated);
+    }
+
+    #[test]
+    fn test_break_even() {
+        assert_eq!(break_even((110.0, 1.0), (10.0, 2.0)), Some(100));
+        assert_eq!(break_even((110.0, 1.0), (10.0, 1.5)), Some(200));
+        assert_eq!(break_even((10.0, 1.0), (10.0, 2.0)), Some(0));
+        assert_eq!(break_even((20.0, 1.0), (10.0, 1.0)), None);
+
+        let row = |runtime: &str, backend: &str, phase: &str, mean: f64| Row {
+            runtime: runtime.to_owned(),
+            backend: backend.to_owned(),
+            sample: "fibonacci".to_owned(),
+            phase: phase.to_owned(),
+            input: "10".to_owned(),
+            status: "measured".to_owned(),
+            mean: Some(mean),
+            ..Row::default()
+        };
+        let results = Results::from_rows(vec![
+            row("wasmer", "singlepass", "compile", 6_774_600.0),
+            row("wasmer", "singlepass", "instantiate", 18_522.0),
+            row("wasmer", "singlepass", "execute", 6_860.1),
+            row("wasmer", "llvm", "compile", 9_109_700_000.0),
+            row("wasmer", "llvm", "instantiate", 35_125.0),
+            row("wasmer", "llvm", "execute", 1_954.9),
+            row("lucet", "", "compile", 101_630_000.0),
+            row("lucet", "", "instantiate", 157_930.0),
+            row("lucet", "", "execute", 11_104.0),
+            // No instantiate
+            row("wasmer", "cranelift", "compile", 16_133_000.0),
+            row("wasmer", "cranelift", "execute", 2_404.7),
+        ]);
+        let samples = samples();
+        let report = Report::new(&results, &samples);
+        assert_eq!(
+            report.render_table("break_even").unwrap().render(),
+            "\
+|           | faster            | than              | break-even (calls) |
+| --------- | ----------------- | ----------------- | ------------------ |
+| fibonacci | wasmer/llvm       | wasmer/singlepass | 1855774            |
+| fibonacci | wasmer/singlepass | lucet             | always             |
+| fibonacci | wasmer/llvm       | lucet             | 984572             |
+"
+        );
+    }
+
+    #[test]
+    fn test_scaling() {
+        let row =
+            |runtime: &str, backend: &str, bytes: u64, mean: f64, artifact: Option<u64>| Row {
+                runtime: runtime.to_owned(),
+                backend: backend.to_owned(),
+                sample: "synth-functions".to_owned(),
+                phase: "compile".to_owned(),
+                input: bytes.to_string(),
+                status: "measured".to_owned(),
+                mean: Some(mean),
+                wasm_bytes: Some(bytes),
+                artifact_bytes: artifact,
+                ..Row::default()
+            };
+        let results = Results::from_rows(vec![
+            row("wasmer", "singlepass", 40, 150_000.0, Some(9_000)),
+            row("wasmer", "singlepass", 12_000, 2_500_000.0, Some(310_000)),
+            row("lucet", "", 12_000, 90_000_000.0, None),
+        ]);
+        let samples = samples();
+        let report = Report::new(&results, &samples);
+        assert_eq!(
+            report.render_table("compile_scaling").unwrap().render(),
+            "\
+|           | module | wasmer/singlepass | wasmer/cranelift | wasmer/llvm | lucet     |
+| --------- | ------ | ----------------- | ---------------- | ----------- | --------- |
+| functions | 40 B   | 150.00 us         | /                | /           | /         |
+| functions | 12 K   | 2.5000 ms         | /                | /           | 90.000 ms |
+"
+        );
+        assert!(report
+            .render_table("compile_scaling_space")
+            .unwrap()
+            .render()
+            .contains("| functions | 12 K   | 303 K             | /                | /           | /     |"));
+
+        let html = report.html();
+        assert!(html.contains("<h2>compile time by functions</h2>"));
+        assert!(html.contains("<h2>artifact size by functions</h2>"));
+        assert!(!html.contains("by depth"));
+        assert_eq!(html.matches("<polyline").count(), 2 * WASM_RUNTIMES.len());
+    }
+
+    #[test]
+    fn test_measurement() {
+        let row = |runtime: &str, phase: &str, status: &str, measurement: &str, mean: f64| Row {
+            runtime: runtime.to_owned(),
+            sample: "add-one".to_owned(),
+            phase: phase.to_owned(),
+            input: "10".to_owned(),
+            status: status.to_owned(),
+            measurement: measurement.to_owned(),
+            mean: Some(mean),
+            ..Row::default()
+        };
+        let results = Results::from_rows(vec![
+            row("lucet", "instantiate", "measured", "minor-faults", 312.0),
+            row("lucet", "execute", "sampled", "wall", 9_828.8),
+        ]);
+        let samples = samples();
+        let report = Report::new(&results, &samples);
+        assert_eq!(
+            report.render_table("lucet").unwrap().render(),
+            "\
+|         | a. parse | ab. compile | c. instantiate      | d. execute |
+| ------- | -------- | ----------- | ------------------- | ---------- |
+| add-one | /        | /           | 312.00 minor-faults | ~9.8288 us |
+"
+        );
+
+        // The plain iterations were timed, they get no bar among the counts
+        let html = report.html();
+        assert!(html.contains("<p>measurement: minor-faults</p>"));
+        assert!(html.contains("<h2>instantiate</h2>"));
+        assert!(!html.contains("<h2>execute</h2>"));
+        assert!(html.contains(">100</text>"));
+    }
+
+    #[test]
+    fn test_html() {
+        let (results, samples) = (results(), samples());
+        let html = Report::new(&results, &samples).html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p>Intel(R) Core(TM) i7-8559U CPU @ 2.70GHz x8, "));
+        // The timed out Lucet compile has no bar, there are no results for the
+        // other phases nor both sides of a scatter
+        let headings: Vec<&str> = html.matches("<h2>").collect();
+        assert_eq!(headings.len(), 3);
+        assert!(html.contains("<h2>aot_compile</h2>"));
+        assert_eq!(html.matches("<svg ").count(), 3);
+        assert!(!html.contains("<circle"));
+    }
+}
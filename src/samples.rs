@@ -0,0 +1,296 @@
+use crate::matrix::Phase;
+use crate::trap::TrapKind;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_EXPORT: &str = "run";
+const DEFAULT_INPUT: u32 = 10;
+const MANIFEST: &str = "samples.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeClass {
+    Small,
+    Medium,
+    Large,
+}
+
+impl SizeClass {
+    fn of(bytes: usize) -> Self {
+        if bytes < 10 * 1024 {
+            SizeClass::Small
+        } else if bytes < 100 * 1024 {
+            SizeClass::Medium
+        } else {
+            SizeClass::Large
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SizeClass::Small => "small",
+            SizeClass::Medium => "medium",
+            SizeClass::Large => "large",
+        }
+    }
+}
+
+impl fmt::Display for SizeClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+pub struct Sample {
+    pub name: String,
+    pub wasm: Vec<u8>,
+    pub export: String,
+    pub args: Vec<u32>,
+    // Inputs the single argument is swept over in the execute phase
+    pub inputs: Vec<u32>,
+    // Whether the work grows linearly with the input, which is then reported as
+    // throughput
+    pub linear: bool,
+    pub expected: Option<u32>,
+    // Name of the native implementation to compare pure execution with
+    pub native: Option<String>,
+    pub size: SizeClass,
+    // Phases the sample takes part in, all of them when empty
+    pub phases: Vec<Phase>,
+    // Directory of the Rust crate the binary is built from, relative to the samples
+    pub source: Option<String>,
+    // Kind of trap calling the export ends in, for the samples of
+    // `wasm-sample/traps`
+    pub trap: Option<TrapKind>,
+}
+
+impl Sample {
+    pub fn new(name: &str, wasm: &[u8]) -> Self {
+        Self {
+            name: name.to_owned(),
+            wasm: wasm.to_vec(),
+            export: DEFAULT_EXPORT.to_owned(),
+            args: vec![DEFAULT_INPUT],
+            inputs: vec![],
+            linear: false,
+            expected: None,
+            native: None,
+            size: SizeClass::of(wasm.len()),
+            phases: vec![],
+            source: None,
+            trap: None,
+        }
+    }
+
+    pub fn call(mut self, export: &str, args: &[u32]) -> Self {
+        self.export = export.to_owned();
+        self.args = args.to_vec();
+        self
+    }
+
+    pub fn only(mut self, phases: &[Phase]) -> Self {
+        self.phases = phases.to_vec();
+        self
+    }
+
+    pub fn in_phase(&self, phase: Phase) -> bool {
+        self.phases.is_empty() || self.phases.contains(&phase)
+    }
+}
+
+// An entry of `wasm-sample/samples.toml`, keyed by the file name without extension
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Entry {
+    export: Option<String>,
+    args: Option<Vec<u32>>,
+    inputs: Option<Vec<u32>>,
+    linear: bool,
+    expected: Option<u32>,
+    native: Option<String>,
+    size: Option<SizeClass>,
+    groups: Option<Vec<String>>,
+    source: Option<String>,
+    trap: Option<TrapKind>,
+}
+
+impl Entry {
+    fn apply(self, mut sample: Sample) -> Result<Sample, String> {
+        if let Some(export) = self.export {
+            sample.export = export;
+        }
+        if let Some(args) = self.args {
+            sample.args = args;
+        }
+        if let Some(inputs) = self.inputs {
+            if sample.args.len() != 1 {
+                return Err("inputs need an export taking a single argument".to_owned());
+            }
+            sample.inputs = inputs;
+        }
+        if let Some(size) = self.size {
+            sample.size = size;
+        }
+        if let Some(groups) = self.groups {
+            sample.phases = groups
+                .iter()
+                .map(|g| g.parse())
+                .collect::<Result<_, String>>()?;
+        }
+        sample.linear = self.linear;
+        sample.expected = self.expected;
+        sample.native = self.native;
+        sample.source = self.source;
+        sample.trap = self.trap;
+        Ok(sample)
+    }
+}
+
+pub fn default_dir() -> PathBuf {
+    std::env::var_os("BENCH_SAMPLE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("wasm-sample"))
+}
+
+// Reads a binary module, or compiles a `.wat` text module to binary
+pub fn read_module(path: &Path) -> Result<Vec<u8>, String> {
+    let wasm = if path.extension() == Some(OsStr::new("wat")) {
+        wat::parse_file(path).map_err(|e| e.to_string())?
+    } else {
+        fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+    Ok(wasm)
+}
+
+// Loads every `.wasm` and `.wat` file at the top of `dir`, described by the manifest in
+// `dir/samples.toml` when there is one. Samples are sorted by name.
+pub fn load(dir: &Path) -> Result<Vec<Sample>, String> {
+    let manifest_path = dir.join(MANIFEST);
+    let mut manifest: BTreeMap<String, Entry> = if manifest_path.exists() {
+        let source = fs::read_to_string(&manifest_path).map_err(|e| e.to_string())?;
+        toml::from_str(&source).map_err(|e| format!("{}: {}", manifest_path.display(), e))?
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut samples = vec![];
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("wasm") | Some("wat") => {}
+            _ => continue,
+        }
+        let wasm = read_module(&path)?;
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        if samples.iter().any(|s: &Sample| s.name == name) {
+            return Err(format!("{}: duplicate sample {}", dir.display(), name));
+        }
+        let sample = Sample::new(&name, &wasm);
+        let sample = match manifest.remove(&name) {
+            Some(entry) => entry
+                .apply(sample)
+                .map_err(|e| format!("{}: [{}] {}", manifest_path.display(), name, e))?,
+            None => sample,
+        };
+        samples.push(sample);
+    }
+
+    if let Some(name) = manifest.keys().next() {
+        return Err(format!(
+            "{}: no sample file for [{}]",
+            manifest_path.display(),
+            name
+        ));
+    }
+
+    samples.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(samples)
+}
+
+pub fn load_default() -> Result<Vec<Sample>, String> {
+    load(&default_dir())
+}
+
+// The samples which trap, kept apart so the benchmarks never call them
+pub fn load_traps() -> Result<Vec<Sample>, String> {
+    load(&default_dir().join("traps"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasmer_runner::Wrapper;
+    use wasmer_runtime::Backend;
+
+    #[test]
+    fn test_load_default() {
+        let samples = load_default().unwrap();
+        let names: Vec<&str> = samples.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "add-one",
+                "call-indirect",
+                "fibonacci",
+                "loop",
+                "memory-grow",
+                "mruby-script",
+                "nbody"
+            ]
+        );
+
+        let mruby = &samples[5];
+        assert_eq!(mruby.size, SizeClass::Large);
+        assert!(mruby.in_phase(Phase::AotCompile));
+        assert!(!mruby.in_phase(Phase::Execute));
+        assert_eq!(mruby.native, None);
+        assert_eq!(samples[2].source.as_deref(), Some("fibonacci"));
+        assert_eq!(samples[2].inputs, vec![10, 15, 20, 25, 30]);
+        assert!(samples[6].linear);
+        assert!(!samples[4].in_phase(Phase::Execute));
+        assert!(samples[4].in_phase(Phase::Jit));
+        assert!(samples.iter().all(|s| s.trap.is_none()));
+    }
+
+    #[test]
+    fn test_load_traps() {
+        let samples = load_traps().unwrap();
+        let kinds: Vec<_> = samples.iter().map(|s| (s.name.as_str(), s.trap)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("divide-by-zero", Some(TrapKind::DivideByZero)),
+                ("out-of-bounds", Some(TrapKind::OutOfBounds)),
+                ("stack-overflow", Some(TrapKind::StackOverflow)),
+                ("unreachable", Some(TrapKind::Unreachable)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_module() {
+        let dir = default_dir();
+        let wasm = read_module(&dir.join("loop.wat")).unwrap();
+        assert_eq!(&wasm[..4], b"\0asm");
+        assert!(read_module(&dir.join("missing.wat")).is_err());
+    }
+
+    #[test]
+    fn test_expected() {
+        let wrapper = Wrapper::new(Backend::Singlepass);
+        for sample in load_default().unwrap() {
+            if let Some(expected) = sample.expected {
+                let instance = wrapper.prepare(&sample.wasm).unwrap();
+                let v = wrapper
+                    .call(&instance, &sample.export, &sample.args)
+                    .unwrap();
+                assert_eq!(v, expected, "{}", sample.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::{self, Write};
+
+// The shape of a generated module: `functions` functions each with `locals` i32
+// locals and `body` statements, nested `depth` blocks deep. The exported `run`
+// chains calls through all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shape {
+    pub functions: u32,
+    pub body: u32,
+    pub locals: u32,
+    pub depth: u32,
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Self {
+            functions: 1,
+            body: 10,
+            locals: 1,
+            depth: 1,
+        }
+    }
+}
+
+impl Shape {
+    pub fn wat(&self) -> String {
+        let mut wat = String::from("(module\n");
+        for i in 0..self.functions {
+            self.function(&mut wat, i).unwrap();
+        }
+
+        wat.push_str("  (func (export \"run\") (param i32) (result i32)\n    local.get 0\n");
+        for i in 0..self.functions {
+            writeln!(wat, "    call $f{}", i).unwrap();
+        }
+        wat.push_str("  )\n)\n");
+        wat
+    }
+
+    pub fn wasm(&self) -> Vec<u8> {
+        wat::parse_str(self.wat()).expect("generated an invalid module")
+    }
+
+    // Local 0 is the parameter, statements cycle through the others adding to
+    // them, with a branch out of the outermost block in between
+    fn function(&self, wat: &mut String, index: u32) -> fmt::Result {
+        let locals = self.locals.max(1);
+        write!(wat, "  (func $f{} (param i32) (result i32)", index)?;
+        for _ in 0..locals {
+            wat.push_str(" (local i32)");
+        }
+        wat.push('\n');
+
+        let depth = self.depth.max(1);
+        for level in 0..depth {
+            // The outermost one has to be a block, branching to a loop goes back
+            // to its start
+            let kind = if level % 2 == 0 { "block" } else { "loop" };
+            writeln!(wat, "    {}", kind)?;
+        }
+        for s in 0..self.body {
+            let local = 1 + s % locals;
+            writeln!(
+                wat,
+                "    local.get {l} local.get 0 i32.add i32.const {c} i32.xor local.set {l}",
+                l = local,
+                c = s + index
+            )?;
+            if s % 8 == 7 {
+                writeln!(wat, "    local.get 0 i32.eqz br_if {}", depth - 1)?;
+            }
+        }
+        for _ in 0..depth {
+            wat.push_str("    end\n");
+        }
+
+        wat.push_str("    local.get 0\n");
+        for local in 1..=locals {
+            writeln!(wat, "    local.get {} i32.add", local)?;
+        }
+        wat.push_str("  )\n");
+        Ok(())
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "functions={},body={},locals={},depth={}",
+            self.functions, self.body, self.locals, self.depth
+        )
+    }
+}
+
+// One dimension of the module shape, swept while the others stay small
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Functions,
+    Body,
+    Locals,
+    Depth,
+}
+
+impl Dimension {
+    pub const ALL: &'static [Dimension] = &[
+        Dimension::Functions,
+        Dimension::Body,
+        Dimension::Locals,
+        Dimension::Depth,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Dimension::Functions => "functions",
+            Dimension::Body => "body",
+            Dimension::Locals => "locals",
+            Dimension::Depth => "depth",
+        }
+    }
+
+    pub fn steps(self) -> &'static [u32] {
+        match self {
+            Dimension::Functions => &[1, 10, 100, 1000, 5000],
+            Dimension::Body => &[10, 100, 1000, 10_000, 50_000],
+            Dimension::Locals => &[1, 10, 100, 1000],
+            Dimension::Depth => &[1, 10, 100, 500],
+        }
+    }
+
+    pub fn shape(self, step: u32) -> Shape {
+        let base = Shape {
+            body: 100,
+            ..Shape::default()
+        };
+        match self {
+            Dimension::Functions => Shape {
+                functions: step,
+                ..Shape::default()
+            },
+            Dimension::Body => Shape { body: step, ..base },
+            Dimension::Locals => Shape {
+                locals: step,
+                ..base
+            },
+            Dimension::Depth => Shape {
+                depth: step,
+                ..base
+            },
+        }
+    }
+
+    pub fn shapes(self) -> Vec<Shape> {
+        self.steps().iter().map(|&s| self.shape(s)).collect()
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasmer_runner::Wrapper;
+    use wasmer_runtime::Backend;
+
+    #[test]
+    fn test_shapes() {
+        for &dimension in Dimension::ALL {
+            let sizes: Vec<usize> = dimension.shapes().iter().map(|s| s.wasm().len()).collect();
+            assert!(sizes.windows(2).all(|w| w[0] < w[1]), "{}", dimension);
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let shape = Shape {
+            functions: 3,
+            body: 20,
+            locals: 4,
+            depth: 3,
+        };
+        let wrapper = Wrapper::new(Backend::Singlepass);
+        let instance = wrapper.prepare(&shape.wasm()).unwrap();
+        wrapper.call(&instance, "run", &[10]).unwrap();
+        // Takes the branch out
+        wrapper.call(&instance, "run", &[0]).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+// Formats a time in nanoseconds the way criterion prints it, eg. `907.89 us`
+pub fn format_time(ns: f64) -> String {
+    let (value, unit) = if ns < 1.0 {
+        (ns * 1e3, "ps")
+    } else if ns < 1e3 {
+        (ns, "ns")
+    } else if ns < 1e6 {
+        (ns / 1e3, "us")
+    } else if ns < 1e9 {
+        (ns / 1e6, "ms")
+    } else {
+        (ns / 1e9, "s")
+    };
+    format!("{} {}", short(value), unit)
+}
+
+// Formats a size the way `ls -sh` does, eg. `2.1 K`
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "K", "M", "G"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || value >= 10.0 {
+        format!("{:.0} {}", value, units[unit])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+// Formats a count with an SI prefix, eg. `1.5200 k`
+pub fn format_count(n: f64) -> String {
+    let (value, prefix) = if n < 1e3 {
+        (n, "")
+    } else if n < 1e6 {
+        (n / 1e3, " k")
+    } else if n < 1e9 {
+        (n / 1e6, " M")
+    } else {
+        (n / 1e9, " G")
+    };
+    format!("{}{}", short(value), prefix)
+}
+
+fn short(n: f64) -> String {
+    if n < 10.0 {
+        format!("{:.4}", n)
+    } else if n < 100.0 {
+        format!("{:.3}", n)
+    } else if n < 1000.0 {
+        format!("{:.2}", n)
+    } else if n < 10000.0 {
+        format!("{:.1}", n)
+    } else {
+        format!("{:.0}", n)
+    }
+}
+
+// A markdown table with padded columns, as used in the README
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new<S: ToString>(header: &[S]) -> Self {
+        Self {
+            header: header.iter().map(ToString::to_string).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn row<S: ToString>(&mut self, cells: &[S]) {
+        self.rows
+            .push(cells.iter().map(ToString::to_string).collect());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn render(&self) -> String {
+        let columns = self.header.len();
+        let width = |i: usize| {
+            self.rows
+                .iter()
+                .filter_map(|r| r.get(i))
+                .chain(std::iter::once(&self.header[i]))
+                .map(|c| c.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(3)
+        };
+        let widths: Vec<usize> = (0..columns).map(width).collect();
+
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = (0..columns)
+                .map(|i| {
+                    let cell = cells.get(i).map(String::as_str).unwrap_or("");
+                    format!("{:<w$}", cell, w = widths[i])
+                })
+                .collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+
+        let mut out = line(&self.header);
+        let dashes: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        out.push_str(&format!("| {} |\n", dashes.join(" | ")));
+        for row in &self.rows {
+            out.push_str(&line(row));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(907_890.0), "907.89 us");
+        assert_eq!(format_time(1_562.2), "1.5622 us");
+        assert_eq!(format_time(0.7022), "702.20 ps");
+        assert_eq!(format_time(1_167_400_000.0), "1.1674 s");
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(2_150), "2.1 K");
+        assert_eq!(format_size(41 * 1024), "41 K");
+        assert_eq!(format_size(1_258_291), "1.2 M");
+        assert_eq!(format_size(100), "100 B");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut table = Table::new(&["", "ab. compile"]);
+        table.row(&["add-one", "907.89 us"]);
+        table.row(&["fibonacci", "6.7746 ms"]);
+        assert_eq!(
+            table.render(),
+            "\
+|           | ab. compile |
+| --------- | ----------- |
+| add-one   | 907.89 us   |
+| fibonacci | 6.7746 ms   |
+"
+        );
+    }
+}
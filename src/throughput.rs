@@ -0,0 +1,185 @@
+use crate::dispatch::black_box;
+use crate::latency::percentile;
+use crate::lucet_runner::{self, Shared};
+use crate::matrix::Runtime;
+use crate::wasmer_runner::Wrapper;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Requests served by a number of threads in a while
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throughput {
+    pub threads: usize,
+    pub requests: usize,
+    pub elapsed: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+}
+
+impl Throughput {
+    pub fn per_second(&self) -> f64 {
+        self.requests as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+// Runs `threads` threads serving requests for `duration`, or until each served
+// `limit` of them. Each makes its own request handler with `worker` first, eg.
+// sharing a compiled module, and they all start serving together.
+pub fn measure<F, R>(
+    threads: usize,
+    duration: Duration,
+    limit: Option<usize>,
+    worker: F,
+) -> Throughput
+where
+    F: Fn() -> R + Send + Sync + 'static,
+    R: FnMut(),
+{
+    let worker = Arc::new(worker);
+    let barrier = Arc::new(Barrier::new(threads));
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let (worker, barrier) = (worker.clone(), barrier.clone());
+            thread::spawn(move || {
+                let mut request = worker();
+                let mut latencies = vec![];
+                barrier.wait();
+                let start = Instant::now();
+                while start.elapsed() < duration && Some(latencies.len()) != limit {
+                    let begin = Instant::now();
+                    request();
+                    latencies.push(begin.elapsed());
+                }
+                (latencies, start.elapsed())
+            })
+        })
+        .collect();
+
+    let mut latencies = vec![];
+    let mut elapsed = Duration::default();
+    for handle in handles {
+        let (thread_latencies, thread_elapsed) = handle.join().unwrap();
+        latencies.extend(thread_latencies);
+        elapsed = elapsed.max(thread_elapsed);
+    }
+    latencies.sort();
+    Throughput {
+        threads,
+        requests: latencies.len(),
+        elapsed,
+        p50: percentile(&latencies, 0.5),
+        p99: percentile(&latencies, 0.99),
+    }
+}
+
+// 1, 2, 4, ... up to the number of cores, and the number of cores itself
+pub fn default_threads() -> Vec<usize> {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut threads: Vec<usize> = (0..).map(|i| 1 << i).take_while(|n| *n < cores).collect();
+    threads.push(cores);
+    threads
+}
+
+// Compiles `wasm` once and measures serving requests from it on each number of
+// threads, a request being a fresh instance and a call of `export`, up to
+// `limit` requests per thread. Lucet's region has a slot per thread.
+pub fn serve(
+    runtime: Runtime,
+    wasm: &[u8],
+    export: &str,
+    args: &[u32],
+    threads: &[usize],
+    duration: Duration,
+    limit: Option<usize>,
+) -> Vec<Throughput> {
+    let (export, args) = (Arc::new(export.to_owned()), Arc::new(args.to_vec()));
+    match runtime {
+        Runtime::Native => panic!("{} has no module to share", runtime),
+        Runtime::Lucet => {
+            let slots = threads.iter().copied().max().unwrap_or(1);
+            let shared = Arc::new(Shared::new(&lucet_runner::compile(wasm), slots));
+            let worker = move || {
+                let (shared, export, args) = (shared.clone(), export.clone(), args.clone());
+                move || {
+                    let mut instance = shared.instantiate();
+                    black_box(lucet_runner::call(&mut instance, &export, &args));
+                }
+            };
+            threads
+                .iter()
+                .map(|&n| measure(n, duration, limit, worker.clone()))
+                .collect()
+        }
+        _ => {
+            let backend = runtime.backend().unwrap();
+            let module = Wrapper::new(backend).compile(wasm);
+            let worker = move || {
+                let (wrapper, module) = (Wrapper::new(backend), module.clone());
+                let (export, args) = (export.clone(), args.clone());
+                move || {
+                    let instance = wrapper.instantiate(&module).unwrap();
+                    black_box(wrapper.call(&instance, &export, &args).unwrap());
+                }
+            };
+            threads
+                .iter()
+                .map(|&n| measure(n, duration, limit, worker.clone()))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static WASM: &[u8] = include_bytes!("../wasm-sample/fibonacci.wasm");
+
+    #[test]
+    fn test_measure() {
+        let workers = Arc::new(AtomicUsize::new(0));
+        let counter = workers.clone();
+        let throughput = measure(3, Duration::from_millis(50), None, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            || thread::sleep(Duration::from_millis(1))
+        });
+        assert_eq!(workers.load(Ordering::SeqCst), 3);
+        assert_eq!(throughput.threads, 3);
+        assert!(throughput.requests >= 3, "{:?}", throughput);
+        assert!(throughput.elapsed >= Duration::from_millis(50));
+        assert!(throughput.p50 >= Duration::from_millis(1));
+        assert!(throughput.p99 >= throughput.p50);
+        assert!(throughput.per_second() > 0.0);
+
+        let throughput = measure(3, Duration::from_secs(60), Some(2), || || ());
+        assert_eq!(throughput.requests, 6);
+        assert!(throughput.elapsed < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_default_threads() {
+        let threads = default_threads();
+        assert_eq!(threads[0], 1);
+        assert!(threads.windows(2).all(|w| w[0] < w[1]), "{:?}", threads);
+    }
+
+    #[test]
+    fn test_serve() {
+        for &runtime in &[Runtime::WasmerSinglepass, Runtime::Lucet] {
+            let results = serve(
+                runtime,
+                WASM,
+                "run",
+                &[10],
+                &[1, 2],
+                Duration::from_millis(20),
+                None,
+            );
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[1].threads, 2);
+            assert!(results.iter().all(|r| r.requests > 0));
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use crate::lucet_runner;
+use crate::matrix::Runtime;
+use crate::wasmer_runner::Wrapper;
+use serde::Deserialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+use wasmer_runtime::Instance;
+
+// Export of every trap sample which returns 1 without trapping, called on the
+// instance a trap was just raised in to see whether it still works
+pub const CHECK: &str = "ok";
+
+// What stopped a call. Runtimes which don't tell integer division by zero and
+// overflow apart report `Arithmetic` for both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrapKind {
+    Unreachable,
+    DivideByZero,
+    IntegerOverflow,
+    Arithmetic,
+    OutOfBounds,
+    IndirectCall,
+    StackOverflow,
+    Other,
+}
+
+impl TrapKind {
+    pub const ALL: [TrapKind; 8] = [
+        TrapKind::Unreachable,
+        TrapKind::DivideByZero,
+        TrapKind::IntegerOverflow,
+        TrapKind::Arithmetic,
+        TrapKind::OutOfBounds,
+        TrapKind::IndirectCall,
+        TrapKind::StackOverflow,
+        TrapKind::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TrapKind::Unreachable => "unreachable",
+            TrapKind::DivideByZero => "divide_by_zero",
+            TrapKind::IntegerOverflow => "integer_overflow",
+            TrapKind::Arithmetic => "arithmetic",
+            TrapKind::OutOfBounds => "out_of_bounds",
+            TrapKind::IndirectCall => "indirect_call",
+            TrapKind::StackOverflow => "stack_overflow",
+            TrapKind::Other => "other",
+        }
+    }
+
+    // Whether a trap of this kind is the `expected` one, `Arithmetic` standing
+    // for both integer kinds
+    pub fn is(self, expected: TrapKind) -> bool {
+        self == expected
+            || (self == TrapKind::Arithmetic
+                && matches!(expected, TrapKind::DivideByZero | TrapKind::IntegerOverflow))
+    }
+
+    // The fallback for traps without a code: Wasmer describes a trap in words,
+    // eg. `memory out-of-bounds access`, and Lucet by the name of its trap
+    // code, eg. `Some(HeapOutOfBounds)`. Table and signature errors of
+    // `call_indirect` are checked before plain out-of-bounds accesses.
+    pub fn classify(message: &str) -> Self {
+        let words: String = message
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let has = |keys: &[&str]| keys.iter().any(|k| words.contains(k));
+
+        if has(&["stackoverflow", "stackexhausted"]) {
+            TrapKind::StackOverflow
+        } else if has(&["indirect", "badsignature", "tableoutofbounds"]) {
+            TrapKind::IndirectCall
+        } else if has(&["unreachable"]) {
+            TrapKind::Unreachable
+        } else if has(&["divbyzero", "dividebyzero"]) {
+            TrapKind::DivideByZero
+        } else if has(&["integeroverflow"]) {
+            TrapKind::IntegerOverflow
+        } else if has(&["arithmetic"]) {
+            TrapKind::Arithmetic
+        } else if has(&["outofbounds"]) {
+            TrapKind::OutOfBounds
+        } else {
+            TrapKind::Other
+        }
+    }
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for TrapKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TrapKind::ALL
+            .iter()
+            .copied()
+            .find(|k| k.name() == s)
+            .ok_or_else(|| format!("unknown trap kind {}", s))
+    }
+}
+
+// A call which didn't return, with the runtime's own description of why
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub message: String,
+}
+
+impl Trap {
+    pub fn new(message: String) -> Self {
+        Self {
+            kind: TrapKind::classify(&message),
+            message,
+        }
+    }
+
+    // A trap whose kind the runtime told, rather than its message
+    pub fn with_kind(kind: TrapKind, message: String) -> Self {
+        Self { kind, message }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+// How a runtime dealt with a trapping call: the kind of the trap, the mean time
+// from the call until the error was back, and whether the instance it was
+// raised in still returned from `CHECK` afterwards
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handled {
+    pub kind: TrapKind,
+    pub latency: Duration,
+    pub usable: bool,
+}
+
+impl Handled {
+    // What the child prints on stdout when it is done, `kind latency_ns usable`
+    pub fn report(&self) -> String {
+        format!("{} {} {}", self.kind, self.latency.as_nanos(), self.usable)
+    }
+
+    pub fn parse(stdout: &str) -> Option<Self> {
+        let mut parts = stdout.trim().rsplit('\n').next()?.split(' ');
+        let kind = parts.next()?.parse().ok()?;
+        let ns: u64 = parts.next()?.parse().ok()?;
+        let usable = parts.next()?.parse().ok()?;
+        Some(Self {
+            kind,
+            latency: Duration::from_nanos(ns),
+            usable,
+        })
+    }
+}
+
+// Calls `export` on `iterations` fresh instances, timing each call until the
+// trap is back, then calls `CHECK` on the last of them. Fails when a call
+// returns instead of trapping.
+pub fn measure<I, N, C>(
+    iterations: u32,
+    export: &str,
+    args: &[u32],
+    mut instantiate: N,
+    mut call: C,
+) -> Result<Handled, String>
+where
+    N: FnMut() -> I,
+    C: FnMut(&mut I, &str, &[u32]) -> Result<u32, Trap>,
+{
+    let iterations = iterations.max(1);
+    let mut kind = TrapKind::Other;
+    let mut latency = Duration::default();
+    let mut instance = None;
+    for i in 0..iterations {
+        let mut fresh = instantiate();
+        let start = Instant::now();
+        let result = call(&mut fresh, export, args);
+        latency += start.elapsed();
+        match result {
+            Ok(v) => return Err(format!("`{}` returned {} instead of trapping", export, v)),
+            Err(trap) if i == 0 => kind = trap.kind,
+            Err(_) => {}
+        }
+        instance = Some(fresh);
+    }
+
+    let usable = call(instance.as_mut().unwrap(), CHECK, &[]) == Ok(1);
+    Ok(Handled {
+        kind,
+        latency: latency / iterations,
+        usable,
+    })
+}
+
+// Compiles `wasm` once and measures how a call of `export` which traps is
+// handled, on fresh instances from the compiled module
+pub fn handle(
+    runtime: Runtime,
+    wasm: &[u8],
+    export: &str,
+    args: &[u32],
+    iterations: u32,
+) -> Result<Handled, String> {
+    match runtime {
+        Runtime::Native => Err(format!("{} has no instances", runtime)),
+        Runtime::Lucet => {
+            let moduleid = lucet_runner::compile(wasm);
+            measure(
+                iterations,
+                export,
+                args,
+                || lucet_runner::instantiate(&moduleid),
+                lucet_runner::try_call,
+            )
+        }
+        _ => {
+            let wrapper = Wrapper::new(runtime.backend().unwrap());
+            let module = wrapper.compile(wasm);
+            measure(
+                iterations,
+                export,
+                args,
+                || wrapper.instantiate(&module).unwrap(),
+                |instance: &mut Instance, export: &str, args: &[u32]| {
+                    wrapper.try_call(instance, export, args)
+                },
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static WASM: &[u8] = include_bytes!("../wasm-sample/fibonacci.wasm");
+
+    #[test]
+    fn test_classify() {
+        for &(message, kind) in &[
+            (
+                "WebAssembly trap occurred during runtime: unreachable",
+                TrapKind::Unreachable,
+            ),
+            ("illegal arithmetic operation", TrapKind::Arithmetic),
+            ("memory out-of-bounds access", TrapKind::OutOfBounds),
+            ("call_indirect out-of-bounds", TrapKind::IndirectCall),
+            ("Some(IntegerDivByZero)", TrapKind::DivideByZero),
+            ("Some(IntegerOverflow)", TrapKind::IntegerOverflow),
+            ("Some(HeapOutOfBounds)", TrapKind::OutOfBounds),
+            ("Some(TableOutOfBounds)", TrapKind::IndirectCall),
+            ("Some(StackOverflow)", TrapKind::StackOverflow),
+            ("None", TrapKind::Other),
+        ] {
+            assert_eq!(TrapKind::classify(message), kind, "{}", message);
+        }
+        for &kind in &TrapKind::ALL {
+            assert_eq!(kind.name().parse(), Ok(kind));
+        }
+        assert!("segfault".parse::<TrapKind>().is_err());
+
+        assert!(TrapKind::Arithmetic.is(TrapKind::DivideByZero));
+        assert!(TrapKind::DivideByZero.is(TrapKind::DivideByZero));
+        assert!(!TrapKind::DivideByZero.is(TrapKind::Arithmetic));
+        assert!(!TrapKind::Other.is(TrapKind::OutOfBounds));
+    }
+
+    #[test]
+    fn test_parse() {
+        let handled = Handled {
+            kind: TrapKind::OutOfBounds,
+            latency: Duration::from_micros(3),
+            usable: true,
+        };
+        assert_eq!(handled.report(), "out_of_bounds 3000 true");
+        assert_eq!(
+            Handled::parse(&format!("compiling\n{}\n", handled.report())),
+            Some(handled)
+        );
+        assert_eq!(Handled::parse("out_of_bounds 3000"), None);
+    }
+
+    // An instance is a flag of whether it trapped before, the export `trap`
+    // traps and leaves the instance broken when `broken`
+    fn run(broken: bool, instance: &mut bool, export: &str) -> Result<u32, Trap> {
+        if export == CHECK && !*instance {
+            return Ok(1);
+        }
+        *instance = broken;
+        Err(Trap::new("unreachable".to_owned()))
+    }
+
+    #[test]
+    fn test_measure() {
+        let mut instances = 0;
+        let handled = measure(
+            3,
+            "trap",
+            &[],
+            || {
+                instances += 1;
+                false
+            },
+            |i, e, _| run(false, i, e),
+        )
+        .unwrap();
+        assert_eq!(instances, 3);
+        assert_eq!(handled.kind, TrapKind::Unreachable);
+        assert!(handled.usable);
+
+        let handled = measure(1, "trap", &[], || false, |i, e, _| run(true, i, e)).unwrap();
+        assert!(!handled.usable);
+
+        let error = measure(1, CHECK, &[], || false, |i, e, _| run(false, i, e)).unwrap_err();
+        assert!(error.contains("instead of trapping"), "{}", error);
+    }
+
+    #[test]
+    fn test_handle() {
+        let samples = crate::samples::load_traps().unwrap();
+        let unreachable = samples.iter().find(|s| s.name == "unreachable").unwrap();
+        for &runtime in &[Runtime::WasmerSinglepass, Runtime::Lucet] {
+            let handled = handle(runtime, &unreachable.wasm, "run", &[10], 2).unwrap();
+            assert_eq!(handled.kind, TrapKind::Unreachable);
+            assert!(handled.latency > Duration::from_secs(0));
+        }
+        assert!(handle(Runtime::Lucet, WASM, "run", &[10], 1).is_err());
+        assert!(handle(Runtime::Native, WASM, "run", &[10], 1).is_err());
+    }
+}
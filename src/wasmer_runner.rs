@@ -1,6 +1,11 @@
+use crate::trap::{Trap, TrapKind};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use wasmer_runtime::error::{CallError, InvokeError, RuntimeError};
 use wasmer_runtime::{
     cache::{Cache, FileSystemCache, WasmHash},
-    compile_with, compiler_for_backend, error, imports, Backend, Func, Instance, Module,
+    compile_with, compiler_for_backend, error, imports, Backend, ExceptionCode, Func, Instance,
+    Module, Value,
 };
 
 pub struct Wrapper {
@@ -16,6 +21,46 @@ pub enum AotError {
 }
 pub type AotResult<T> = std::result::Result<T, AotError>;
 
+// Wasmer has one code for every integer arithmetic trap and none for stack
+// overflows
+fn exception_kind(code: &ExceptionCode) -> TrapKind {
+    match code {
+        ExceptionCode::Unreachable => TrapKind::Unreachable,
+        ExceptionCode::IllegalArithmetic => TrapKind::Arithmetic,
+        ExceptionCode::MemoryOutOfBounds => TrapKind::OutOfBounds,
+        ExceptionCode::CallIndirectOOB | ExceptionCode::IncorrectCallIndirectSignature => {
+            TrapKind::IndirectCall
+        }
+        _ => TrapKind::Other,
+    }
+}
+
+// The kind of a trap from its exception code, which the backends either put in
+// `InvokeError::TrapCode` or unwind with as a `User` payload. Cranelift's trap
+// codes without an `ExceptionCode` come by name. None for traps without either,
+// eg. an unknown signal.
+fn runtime_kind(e: &RuntimeError) -> Option<TrapKind> {
+    match e {
+        RuntimeError::InvokeError(InvokeError::TrapCode { code, .. }) => Some(exception_kind(code)),
+        RuntimeError::InvokeError(InvokeError::UnknownTrapCode { trap_code, .. }) => {
+            Some(TrapKind::classify(trap_code))
+        }
+        RuntimeError::InvokeError(InvokeError::EarlyTrap(e))
+        | RuntimeError::InvokeError(InvokeError::Breakpoint(e)) => runtime_kind(e),
+        RuntimeError::User(payload) => payload.downcast_ref::<ExceptionCode>().map(exception_kind),
+        _ => None,
+    }
+}
+
+fn trap_kind(e: &error::Error) -> Option<TrapKind> {
+    match e {
+        error::Error::RuntimeError(e) | error::Error::CallError(CallError::Runtime(e)) => {
+            runtime_kind(e)
+        }
+        _ => None,
+    }
+}
+
 impl std::convert::From<std::io::Error> for AotError {
     fn from(e: std::io::Error) -> Self {
         Self::IOError(e)
@@ -66,11 +111,14 @@ impl Wrapper {
         Ok(key.encode())
     }
 
+    // Size of the serialized artifact `aot_c` stores in the cache
+    pub fn artifact_size(&self, wasm_bytes: &[u8]) -> AotResult<usize> {
+        let module = self.compile(wasm_bytes);
+        Ok(module.cache()?.serialize()?.len())
+    }
+
     pub fn aot_e(&self, key: &str, arg: u32) -> AotResult<u32> {
-        let fs_cache = unsafe { FileSystemCache::new("./tmp/")? };
-        let module = fs_cache
-            .load_with_backend(WasmHash::decode(key).unwrap(), self.backend)
-            .unwrap();
+        let module = self.load(key)?;
 
         let import_object = imports! {};
         let instance = module.instantiate(&import_object).unwrap();
@@ -85,6 +133,19 @@ impl Wrapper {
         self.aot_e(&key, arg)
     }
 
+    pub fn load(&self, key: &str) -> AotResult<Module> {
+        let fs_cache = unsafe { FileSystemCache::new("./tmp/")? };
+        let module = fs_cache
+            .load_with_backend(WasmHash::decode(key).unwrap(), self.backend)
+            .unwrap();
+        Ok(module)
+    }
+
+    // Parses and validates only, the same for every backend
+    pub fn validate(&self, wasm_bytes: &[u8]) -> bool {
+        wasmer_runtime::validate(wasm_bytes)
+    }
+
     pub fn compile(&self, wasm_bytes: &[u8]) -> Module {
         let compiler = compiler_for_backend(self.backend).unwrap();
         compile_with(&wasm_bytes, compiler.as_ref()).unwrap()
@@ -107,11 +168,113 @@ impl Wrapper {
         let v = func.call(arg)?;
         Ok(v)
     }
+
+    // Starts on an instance compiled with this wrapper's backend right away,
+    // while `optimized` compiles the same bytes on a background thread
+    pub fn tiered(&self, wasm_bytes: &[u8], optimized: Backend) -> error::Result<Tiered> {
+        let (tx, rx) = mpsc::channel();
+        let wasm = wasm_bytes.to_vec();
+        thread::spawn(move || {
+            let compiler = compiler_for_backend(optimized).unwrap();
+            // Nobody is waiting any more when the `Tiered` was dropped first
+            let _ = tx.send(compile_with(&wasm, compiler.as_ref()));
+        });
+
+        Ok(Tiered {
+            backend: self.backend,
+            instance: self.prepare(wasm_bytes)?,
+            pending: Some((optimized, rx)),
+        })
+    }
+
+    // Calls any export taking i32 arguments and returns its first result, or 0
+    // if it has none. Single argument exports go through the typed `Func` like
+    // `execute` does, to keep the dynamic call overhead out of the numbers.
+    pub fn call(&self, instance: &Instance, export: &str, args: &[u32]) -> error::Result<u32> {
+        if let [arg] = args {
+            if let Ok(func) = instance.func::<u32, u32>(export) {
+                return Ok(func.call(*arg)?);
+            }
+        }
+
+        let params: Vec<Value> = args.iter().map(|a| Value::I32(*a as i32)).collect();
+        let v = match instance.call(export, &params)?.first() {
+            Some(Value::I32(v)) => *v as u32,
+            Some(Value::I64(v)) => *v as u32,
+            _ => 0,
+        };
+        Ok(v)
+    }
+
+    // Same as `call`, with the error as a trap. Its kind comes from the error's
+    // exception code where Wasmer gives one, and from its message otherwise.
+    // Errors which aren't traps, like a missing export, end up as
+    // `TrapKind::Other`.
+    pub fn try_call(&self, instance: &Instance, export: &str, args: &[u32]) -> Result<u32, Trap> {
+        self.call(instance, export, args)
+            .map_err(|e| match trap_kind(&e) {
+                Some(kind) => Trap::with_kind(kind, e.to_string()),
+                None => Trap::new(e.to_string()),
+            })
+    }
+}
+
+// An instance which moves up a tier once the higher tier's module is compiled
+pub struct Tiered {
+    backend: Backend,
+    instance: Instance,
+    pending: Option<(Backend, Receiver<error::CompileResult<Module>>)>,
+}
+
+impl Tiered {
+    // Swaps in an instance of the optimized module if it is compiled, or waits
+    // for it with `block`. Failing to compile or instantiate it stays on the
+    // current tier.
+    fn poll(&mut self, block: bool) {
+        let (backend, compiled) = match &self.pending {
+            Some((backend, rx)) if block => (*backend, rx.recv().ok()),
+            Some((backend, rx)) => match rx.try_recv() {
+                Err(TryRecvError::Empty) => return,
+                compiled => (*backend, compiled.ok()),
+            },
+            None => return,
+        };
+        self.pending = None;
+
+        if let Some(Ok(module)) = compiled {
+            let import_object = imports! {};
+            if let Ok(instance) = module.instantiate(&import_object) {
+                self.instance = instance;
+                self.backend = backend;
+            }
+        }
+    }
+
+    // Blocks until the optimized module is compiled and swapped in
+    pub fn wait(&mut self) {
+        self.poll(true);
+    }
+
+    // Backend of the instance calls currently go to
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn execute(&mut self, arg: u32) -> error::Result<u32> {
+        self.poll(false);
+        Wrapper::new(self.backend).execute(&self.instance, arg)
+    }
+
+    pub fn call(&mut self, export: &str, args: &[u32]) -> error::Result<u32> {
+        self.poll(false);
+        Wrapper::new(self.backend).call(&self.instance, export, args)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trap::TrapKind;
 
     static WASM: &'static [u8] = include_bytes!("../wasm-sample/fibonacci.wasm");
 
@@ -130,10 +293,55 @@ mod tests {
         assert_eq!(v, 8);
     }
     #[test]
+    fn test_artifact_size() {
+        let size = wrapper().artifact_size(&WASM).unwrap();
+        assert!(size > 0);
+    }
+    #[test]
+    fn test_validate() {
+        assert!(wrapper().validate(&WASM));
+        assert!(!wrapper().validate(&WASM[..100]));
+    }
+    #[test]
     fn test_execute() {
         let wrapper = wrapper();
         let instance = wrapper.prepare(&WASM).unwrap();
         let v = wrapper.execute(&instance, 5).unwrap();
         assert_eq!(v, 8);
     }
+    #[test]
+    fn test_call() {
+        let wrapper = wrapper();
+        let instance = wrapper.prepare(&WASM).unwrap();
+        assert_eq!(wrapper.call(&instance, "run", &[5]).unwrap(), 8);
+        assert!(wrapper.call(&instance, "missing", &[5]).is_err());
+    }
+    #[test]
+    fn test_try_call() {
+        let wrapper = wrapper();
+        let wasm = wat::parse_file("wasm-sample/traps/unreachable.wat").unwrap();
+        let instance = wrapper.prepare(&wasm).unwrap();
+        let trap = wrapper.try_call(&instance, "run", &[5]).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::Unreachable, "{}", trap);
+        let instance = wrapper.prepare(&wasm).unwrap();
+        assert_eq!(wrapper.try_call(&instance, "ok", &[]), Ok(1));
+
+        for sample in crate::samples::load_traps().unwrap() {
+            let expected = sample.trap.unwrap();
+            let instance = wrapper.prepare(&sample.wasm).unwrap();
+            let trap = wrapper
+                .try_call(&instance, &sample.export, &sample.args)
+                .unwrap_err();
+            assert!(trap.kind.is(expected), "{}: {}", sample.name, trap);
+        }
+    }
+    #[test]
+    fn test_tiered() {
+        let mut tiered = wrapper().tiered(&WASM, Backend::Cranelift).unwrap();
+        assert_eq!(tiered.execute(5).unwrap(), 8);
+        tiered.wait();
+        assert_eq!(tiered.backend(), Backend::Cranelift);
+        assert_eq!(tiered.execute(5).unwrap(), 8);
+        assert_eq!(tiered.call("run", &[6]).unwrap(), 13);
+    }
 }
@@ -0,0 +1,24 @@
+;; Calls through a table n times, adding f[i % 4](i) where f[k](x) = x + k
+(module
+  (type $unary (func (param i32) (result i32)))
+  (table 4 funcref)
+  (elem (i32.const 0) $f0 $f1 $f2 $f3)
+  (func $f0 (type $unary) (local.get 0))
+  (func $f1 (type $unary) (i32.add (local.get 0) (i32.const 1)))
+  (func $f2 (type $unary) (i32.add (local.get 0) (i32.const 2)))
+  (func $f3 (type $unary) (i32.add (local.get 0) (i32.const 3)))
+  (func (export "run") (param $n i32) (result i32)
+    (local $i i32)
+    (local $sum i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $sum
+          (i32.add
+            (local.get $sum)
+            (call_indirect (type $unary)
+              (local.get $i)
+              (i32.rem_u (local.get $i) (i32.const 4)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.get $sum)))
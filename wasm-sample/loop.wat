@@ -0,0 +1,12 @@
+;; Tight loop summing 0..n, to compare the code generated for a hot loop
+(module
+  (func (export "run") (param $n i32) (result i32)
+    (local $i i32)
+    (local $sum i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $sum (i32.add (local.get $sum) (local.get $i)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.get $sum)))
@@ -0,0 +1,14 @@
+;; Grows the memory one page at a time, n times, and returns its size in pages.
+;; Only called on fresh instances, repeated calls on one would keep growing it
+;; up to a heap limit which differs between runtimes.
+(module
+  (memory 1)
+  (func (export "run") (param $n i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (drop (memory.grow (i32.const 1)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (memory.size)))
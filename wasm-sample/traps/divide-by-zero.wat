@@ -0,0 +1,6 @@
+;; Divides by its argument, which is 0
+(module
+  (func (export "run") (param $n i32) (result i32)
+    (i32.div_u (i32.const 100) (local.get $n)))
+  (func (export "ok") (result i32)
+    (i32.const 1)))
@@ -0,0 +1,8 @@
+;; Loads from its argument, an address past the single page of memory. `ok`
+;; reads the memory too, to see that it survived.
+(module
+  (memory 1)
+  (func (export "run") (param $addr i32) (result i32)
+    (i32.load (local.get $addr)))
+  (func (export "ok") (result i32)
+    (i32.add (i32.load (i32.const 0)) (i32.const 1))))
@@ -0,0 +1,9 @@
+;; Fibonacci without the base case, so the recursion never ends. The calls
+;; aren't in tail position, nothing can turn them into a loop.
+(module
+  (func $run (export "run") (param $n i32) (result i32)
+    (i32.add
+      (call $run (i32.sub (local.get $n) (i32.const 1)))
+      (call $run (i32.sub (local.get $n) (i32.const 2)))))
+  (func (export "ok") (result i32)
+    (i32.const 1)))
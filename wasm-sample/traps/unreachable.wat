@@ -0,0 +1,6 @@
+;; Hits `unreachable` right away
+(module
+  (func (export "run") (param $n i32) (result i32)
+    unreachable)
+  (func (export "ok") (result i32)
+    (i32.const 1)))
@@ -0,0 +1,258 @@
+// Maintenance tasks, run with `cargo xtask <task>`
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+const TARGET: &str = "wasm32-unknown-unknown";
+const USAGE: &str = "\
+usage: cargo xtask <task>
+
+tasks:
+  build-samples   rebuild the Rust samples and overwrite the checked-in .wasm files
+  check-samples   rebuild the Rust samples and check they match the checked-in .wasm files
+";
+
+// A sample built from a Rust crate, as listed by `source` in `wasm-sample/samples.toml`
+struct Source {
+    name: String,
+    dir: PathBuf,
+}
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+fn sample_dir() -> PathBuf {
+    root().join("wasm-sample")
+}
+
+fn out_dir() -> PathBuf {
+    env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root().join("target"))
+        .join("wasm-sample")
+}
+
+// The `[name]` tables of the manifest with a `source = "dir"` line, all xtask
+// needs of it. Read by hand so xtask has no dependencies and builds offline with
+// the toolchain pinned in `rust-toolchain`.
+fn parse_sources(manifest: &str) -> Vec<(String, String)> {
+    let mut sources = vec![];
+    let mut name = None;
+    for line in manifest.lines().map(str::trim) {
+        if let Some(table) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            name = Some(table.trim().to_owned());
+        } else if let Some((key, value)) = line.split_once('=') {
+            if key.trim() != "source" {
+                continue;
+            }
+            if let (Some(name), Some(source)) = (&name, value.trim().strip_prefix('"')) {
+                let source = source.split('"').next().unwrap_or_default();
+                sources.push((name.clone(), source.to_owned()));
+            }
+        }
+    }
+    sources
+}
+
+fn sources() -> Result<Vec<Source>, String> {
+    let path = sample_dir().join("samples.toml");
+    let manifest = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(parse_sources(&manifest)
+        .into_iter()
+        .map(|(name, source)| Source {
+            name,
+            dir: sample_dir().join(source),
+        })
+        .collect())
+}
+
+fn rustc() -> String {
+    env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned())
+}
+
+// The toolchain comes from `rust-toolchain`, the target has to be added separately
+fn target_installed() -> Result<bool, String> {
+    let output = Command::new(rustc())
+        .current_dir(root())
+        .args(["--print", "sysroot"])
+        .output()
+        .map_err(|e| format!("{}: {}", rustc(), e))?;
+    let sysroot = String::from_utf8_lossy(&output.stdout);
+    Ok(Path::new(sysroot.trim())
+        .join("lib/rustlib")
+        .join(TARGET)
+        .exists())
+}
+
+// Same as the commands in `wasm-sample/README.md`, with `wasm-strip` done in here
+fn build(source: &Source) -> Result<Vec<u8>, String> {
+    let out = out_dir().join(format!("{}.wasm", source.name));
+    fs::create_dir_all(out.parent().unwrap()).map_err(|e| e.to_string())?;
+
+    let status = Command::new(rustc())
+        .current_dir(&source.dir)
+        .args([
+            "--target",
+            TARGET,
+            "--crate-type",
+            "cdylib",
+            // Explicit, so the output doesn't depend on rustc's default
+            "-C",
+            "opt-level=0",
+            "src/lib.rs",
+            "-o",
+        ])
+        .arg(&out)
+        .status()
+        .map_err(|e| format!("{}: {}", rustc(), e))?;
+    if !status.success() {
+        return Err(format!("{}: rustc failed with {}", source.name, status));
+    }
+
+    let wasm = fs::read(&out).map_err(|e| format!("{}: {}", out.display(), e))?;
+    strip(&wasm).map_err(|e| format!("{}: {}", source.name, e))
+}
+
+fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, String> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or("unexpected end of module")?;
+        *pos += 1;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("invalid section size".to_owned())
+}
+
+// Drops the custom sections (names, producers, debug info), like `wasm-strip`
+fn strip(wasm: &[u8]) -> Result<Vec<u8>, String> {
+    if wasm.len() < 8 || &wasm[..4] != b"\0asm" {
+        return Err("not a wasm module".to_owned());
+    }
+
+    let mut out = wasm[..8].to_vec();
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let start = pos;
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_u32(wasm, &mut pos)? as usize;
+        let end = pos + size;
+        if end > wasm.len() {
+            return Err("unexpected end of module".to_owned());
+        }
+        if id != 0 {
+            out.extend_from_slice(&wasm[start..end]);
+        }
+        pos = end;
+    }
+    Ok(out)
+}
+
+fn build_samples(check: bool) -> Result<bool, String> {
+    if !target_installed()? {
+        println!(
+            "skipped, the {} target is not installed: rustup target add {}",
+            TARGET, TARGET
+        );
+        return Ok(true);
+    }
+
+    let mut matching = true;
+    for source in sources()? {
+        let wasm = build(&source)?;
+        let path = sample_dir().join(format!("{}.wasm", source.name));
+        if !check {
+            fs::write(&path, &wasm).map_err(|e| format!("{}: {}", path.display(), e))?;
+            println!("{}: {} bytes", path.display(), wasm.len());
+            continue;
+        }
+
+        let checked_in = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if checked_in == wasm {
+            println!("{}: ok", source.name);
+        } else {
+            matching = false;
+            println!(
+                "{}: differs, {} bytes checked in, {} bytes built from {}",
+                source.name,
+                checked_in.len(),
+                wasm.len(),
+                source.dir.display()
+            );
+        }
+    }
+    Ok(matching)
+}
+
+fn main() {
+    let result = match env::args().nth(1).as_deref() {
+        Some("build-samples") => build_samples(false),
+        Some("check-samples") => build_samples(true),
+        _ => {
+            eprint!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!("run `cargo xtask build-samples` to update the checked-in samples");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        // custom section "name" with one byte of payload
+        wasm.extend_from_slice(&[0, 6, 4, b'n', b'a', b'm', b'e', 0]);
+        // type section with a single `() -> ()`
+        wasm.extend_from_slice(&[1, 4, 1, 0x60, 0, 0]);
+
+        let stripped = strip(&wasm).unwrap();
+        assert_eq!(&stripped[8..], &[1, 4, 1, 0x60, 0, 0]);
+        assert!(strip(&wasm[..wasm.len() - 1]).is_err());
+        assert!(strip(b"wasm").is_err());
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        let manifest = "\
+# source = \"commented\"
+[add-one]
+args = [10]
+
+[fibonacci]
+args = [10, 20]
+source = \"fibonacci\" # the crate
+";
+        assert_eq!(
+            parse_sources(manifest),
+            vec![("fibonacci".to_owned(), "fibonacci".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_sources() {
+        let names: Vec<String> = sources().unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["fibonacci", "nbody"]);
+    }
+}